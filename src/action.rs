@@ -0,0 +1,317 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::keyboard::{Event, Key, MouseButton, MouseKind};
+
+/// Mouse buttons along with the names used for them in files
+const MOUSE_BUTTONS: [(MouseButton, &str); 5] = [
+    (MouseButton::Left, "MouseLeft"),
+    (MouseButton::Middle, "MouseMiddle"),
+    (MouseButton::Right, "MouseRight"),
+    (MouseButton::Back, "MouseBack"),
+    (MouseButton::Forward, "MouseForward"),
+];
+
+/// Returns whether a name can be read back from a file as an action or axis
+/// name
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(char::is_whitespace)
+        && !name.contains('=')
+        && !name.starts_with('#')
+}
+
+/// Represents an input an action can be bound to
+///
+/// Gamepad buttons are keys as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl From<Key> for Binding {
+    fn from(key: Key) -> Self {
+        Self::Key(key)
+    }
+}
+
+impl From<MouseButton> for Binding {
+    fn from(button: MouseButton) -> Self {
+        Self::Mouse(button)
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key}"),
+            // No button can't be bound, so isn't read back from its name
+            Binding::Mouse(button) => f.write_str(
+                MOUSE_BUTTONS
+                    .iter()
+                    .find(|(other, _)| other == button)
+                    .map_or("MouseNone", |(_, name)| name),
+            ),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some((button, _)) = MOUSE_BUTTONS.iter().find(|(_, other)| *other == name) {
+            return Ok(Binding::Mouse(*button));
+        }
+
+        name.parse()
+            .map(Binding::Key)
+            .or(Err("Unknown binding name"))
+    }
+}
+
+/// Binds named actions to keys, gamepad buttons and mouse buttons, and
+/// named axes to pairs of them.
+///
+/// ```update()``` is called once a frame with the events read that frame,
+/// after which actions can be queried. Bindings are saved as lines of an
+/// action or axis name, ```=```, and its bindings separated by spaces,
+/// with the two ends of an axis joined by ```/```. Lines starting with
+/// ```#``` are ignored.
+///
+/// ```text
+/// # Player one
+/// jump = Space South
+/// fire = LeftCtrl MouseLeft
+/// move_x = A/D Left/Right DPadLeft/DPadRight
+/// ```
+///
+/// # Example
+/// ```
+/// # use a5c11::{action::ActionMap, keyboard::{Event, Key, KeyEvent}};
+/// let mut actions: ActionMap = "jump = Space\nmove_x = A/D".parse().unwrap();
+/// actions.bind("jump", Key::South);
+/// assert_eq!(actions.to_string(), "jump = Space South\nmove_x = A/D\n");
+///
+/// actions.update(&[
+///     Event::Key(KeyEvent::new(Key::Space, true)),
+///     Event::Key(KeyEvent::new(Key::D, true)),
+/// ]);
+/// assert!(actions.just_pressed("jump"));
+/// assert_eq!(actions.axis("move_x"), 1.0);
+///
+/// actions.update(&[Event::Key(KeyEvent::new(Key::Space, false))]);
+/// assert!(actions.just_released("jump"));
+/// assert!(!actions.pressed("jump"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ActionMap {
+    actions: BTreeMap<String, Vec<Binding>>,
+    axes: BTreeMap<String, Vec<(Binding, Binding)>>,
+    held: HashSet<Binding>,
+    went_down: HashSet<Binding>,
+    went_up: HashSet<Binding>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a binding to an action.
+    ///
+    /// Panics if given ```MouseButton::None```, which isn't a button, or a
+    /// name which couldn't be loaded again: an empty one, or one with
+    /// whitespace or ```=``` in it, or starting with ```#```.
+    pub fn bind(&mut self, action: &str, binding: impl Into<Binding>) {
+        assert!(valid_name(action), "Invalid action name");
+        let binding = binding.into();
+        assert!(
+            binding != Binding::Mouse(MouseButton::None),
+            "MouseButton::None can't be bound"
+        );
+
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Adds a pair of bindings to an axis, which moves it towards -1.0 and
+    /// 1.0 respectively.
+    ///
+    /// Panics if either is ```MouseButton::None```, or the name couldn't
+    /// be loaded again, like with ```bind()```.
+    pub fn bind_axis(
+        &mut self,
+        axis: &str,
+        negative: impl Into<Binding>,
+        positive: impl Into<Binding>,
+    ) {
+        assert!(valid_name(axis), "Invalid action name");
+        let pair = (negative.into(), positive.into());
+        assert!(
+            ![pair.0, pair.1].contains(&Binding::Mouse(MouseButton::None)),
+            "MouseButton::None can't be bound"
+        );
+
+        let pairs = self.axes.entry(axis.to_string()).or_default();
+        if !pairs.contains(&pair) {
+            pairs.push(pair);
+        }
+    }
+
+    /// Removes every binding of an action or axis
+    pub fn unbind(&mut self, name: &str) {
+        self.actions.remove(name);
+        self.axes.remove(name);
+    }
+
+    /// Returns the bindings of an action
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the pairs of bindings of an axis
+    pub fn axis_bindings(&self, axis: &str) -> &[(Binding, Binding)] {
+        self.axes.get(axis).map_or(&[], Vec::as_slice)
+    }
+
+    /// Starts a new frame with the events read since the last one
+    pub fn update(&mut self, events: &[Event]) {
+        self.went_down.clear();
+        self.went_up.clear();
+
+        for event in events {
+            let (binding, down) = match event {
+                Event::Key(event) if !event.repeat => (Binding::Key(event.key), event.down),
+                Event::Mouse(event) if event.kind == MouseKind::Press => {
+                    (Binding::Mouse(event.button), true)
+                }
+                Event::Mouse(event) if event.kind == MouseKind::Release => {
+                    (Binding::Mouse(event.button), false)
+                }
+                // Releases aren't seen while the terminal isn't focused
+                Event::FocusLost => {
+                    self.went_up.extend(self.held.drain());
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if down && self.held.insert(binding) {
+                self.went_down.insert(binding);
+            } else if !down && self.held.remove(&binding) {
+                self.went_up.insert(binding);
+            }
+        }
+    }
+
+    /// Returns whether any binding of an action is held
+    pub fn pressed(&self, action: &str) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| self.held.contains(binding))
+    }
+
+    /// Returns whether a binding of an action went down this frame
+    pub fn just_pressed(&self, action: &str) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| self.went_down.contains(binding))
+    }
+
+    /// Returns whether a binding of an action went up this frame, leaving
+    /// none of them held
+    pub fn just_released(&self, action: &str) -> bool {
+        !self.pressed(action)
+            && self
+                .bindings(action)
+                .iter()
+                .any(|binding| self.went_up.contains(binding))
+    }
+
+    /// Returns the value of an axis from -1.0 to 1.0, which is 0.0 when
+    /// both or neither ends of it are held
+    pub fn axis(&self, axis: &str) -> f32 {
+        let held = |binding: &Binding| self.held.contains(binding) as i32 as f32;
+
+        self.axis_bindings(axis)
+            .iter()
+            .map(|(negative, positive)| held(positive) - held(negative))
+            .sum::<f32>()
+            .clamp(-1.0, 1.0)
+    }
+
+    /// Reads bindings from a file
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, &'static str> {
+        std::fs::read_to_string(path)
+            .or(Err("Failed to read bindings"))?
+            .parse()
+    }
+
+    /// Writes the bindings to a file, in a form ```load()``` can read
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), &'static str> {
+        std::fs::write(path, self.to_string()).or(Err("Failed to write bindings"))
+    }
+}
+
+impl FromStr for ActionMap {
+    type Err = &'static str;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut map = Self::new();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, bindings) = line.split_once('=').ok_or("Missing = in bindings")?;
+            let name = name.trim();
+            if !valid_name(name) {
+                return Err("Invalid action name in bindings");
+            }
+
+            for binding in bindings.split_whitespace() {
+                match binding.split_once('/') {
+                    Some((negative, positive)) => map.bind_axis(
+                        name,
+                        negative.parse::<Binding>()?,
+                        positive.parse::<Binding>()?,
+                    ),
+                    None => map.bind(name, binding.parse::<Binding>()?),
+                }
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// Writes the bindings in the form read by ```parse()```
+impl fmt::Display for ActionMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (action, bindings) in &self.actions {
+            write!(f, "{action} =")?;
+            for binding in bindings {
+                write!(f, " {binding}")?;
+            }
+            writeln!(f)?;
+        }
+
+        for (axis, pairs) in &self.axes {
+            write!(f, "{axis} =")?;
+            for (negative, positive) in pairs {
+                write!(f, " {negative}/{positive}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
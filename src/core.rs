@@ -1,125 +1,214 @@
 #![allow(dead_code)]
 
 pub mod rand {
+    use std::cell::RefCell;
     #[cfg(unix)]
     use std::io::Read;
 
-    use super::terminal;
-    use terminal::{size, Element};
+    thread_local! {
+        /// Generator used by the free functions in this module
+        static RNG: RefCell<Rng> = RefCell::new(Rng::new());
+    }
 
-    /// Generates a ```Vec``` of random numbers
+    /// A fast, seedable pseudo-random number generator.
     ///
-    /// # Examples
+    /// This is an implementation of xoshiro256**, so the same seed always
+    /// produces the same sequence of values on every platform. Like the rest
+    /// of this module it is not cryptographically secure, so please don't use
+    /// it for anything like that.
+    ///
+    /// # Example
     /// ```
-    /// let random_chars = rand::<char>(200);
-    /// let random_u8s: Vec<u8> = rand(25);
+    /// # use a5c11::core::rand::Rng;
+    /// let mut a = Rng::seed_from_u64(42);
+    /// let mut b = Rng::seed_from_u64(42);
+    /// assert_eq!(a.rand::<u64>(), b.rand::<u64>());
+    ///
+    /// let character: char = a.rand();
+    /// let chance: f32 = a.rand(); // between 0.0 and 1.0
     /// ```
-    pub fn vec<T: Default + Copy>(len: usize) -> Vec<T> {
-        #[cfg(unix)]
-        {
-            let mut buffer = vec![0u8; len * size_of::<T>()]; // Allocate buffer for N elements of T
-            std::fs::File::open("/dev/urandom")
-                .expect("Failed to open /dev/urandom")
-                .read_exact(&mut buffer)
-                .expect("Failed to read random bytes");
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Rng {
+        state: [u64; 4],
+    }
 
-            let mut result: Vec<T> = Vec::with_capacity(len);
-            unsafe {
-                for chunk in buffer.chunks_exact(size_of::<T>()) {
-                    let mut element: T = T::default();
-                    std::ptr::copy_nonoverlapping(
-                        chunk.as_ptr(),
-                        &mut element as *mut T as *mut u8,
-                        size_of::<T>(),
-                    );
-                    result.push(element);
-                }
+    impl Default for Rng {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Rng {
+        /// Creates a generator seeded from the operating system's source of
+        /// randomness.
+        pub fn new() -> Self {
+            Self::seed_from_u64(entropy())
+        }
+
+        /// Creates a generator which always produces the same sequence of
+        /// values for the same seed.
+        pub fn seed_from_u64(seed: u64) -> Self {
+            // Expand the seed with SplitMix64, as recommended by the authors
+            // of xoshiro, so that similar seeds give unrelated sequences
+            let mut seed = seed;
+            let mut split_mix = || {
+                seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = seed;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^ (z >> 31)
+            };
+
+            Self {
+                state: [split_mix(), split_mix(), split_mix(), split_mix()],
             }
+        }
+
+        /// Generates 64 random bits
+        pub fn next_u64(&mut self) -> u64 {
+            let [s0, s1, s2, s3] = &mut self.state;
+
+            let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+            let t = *s1 << 17;
+
+            *s2 ^= *s0;
+            *s3 ^= *s1;
+            *s1 ^= *s2;
+            *s0 ^= *s3;
+            *s2 ^= t;
+            *s3 = s3.rotate_left(45);
 
             result
         }
 
-        #[cfg(windows)]
-        {
-            use winapi::um::wincrypt::{
-                CryptAcquireContextA, CryptGenRandom, CryptReleaseContext, CRYPT_VERIFYCONTEXT,
-                HCRYPTPROV, PROV_RSA_FULL,
-            };
-            {
-                use std::mem::size_of;
-                use std::ptr::null_mut;
+        /// Generates 32 random bits
+        pub fn next_u32(&mut self) -> u32 {
+            // The upper bits are the highest quality ones
+            (self.next_u64() >> 32) as u32
+        }
 
-                let mut h_provider: HCRYPTPROV = 0;
-                unsafe {
-                    if CryptAcquireContextA(
-                        &mut h_provider,
-                        null_mut(),
-                        null_mut(),
-                        PROV_RSA_FULL,
-                        CRYPT_VERIFYCONTEXT,
-                    ) == 0
-                    {
-                        panic!("Failed to acquire cryptographic context! NOT CYBER SECURE")
-                    }
-                }
+        /// Generates a random value of any type implementing ```Random```
+        pub fn rand<T: Random>(&mut self) -> T {
+            T::random(self)
+        }
 
-                let mut buffer = vec![0u8; len * size_of::<T>()];
-                unsafe {
-                    if CryptGenRandom(h_provider, buffer.len() as u32, buffer.as_mut_ptr()) == 0 {
-                        CryptReleaseContext(h_provider, 0);
-                        panic!("FAILED TO GENERATE RANDOM BYTES");
-                    }
-                }
+        /// Generates a ```Vec``` of random values
+        pub fn vec<T: Random>(&mut self, len: usize) -> Vec<T> {
+            (0..len).map(|_| self.rand()).collect()
+        }
+    }
 
-                unsafe {
-                    CryptReleaseContext(h_provider, 0);
-                }
+    /// Types which can be generated at random by an ```Rng```.
+    ///
+    /// Integers and ```bool```s use every possible value, floats are between
+    /// 0.0 (inclusive) and 1.0 (exclusive), and ```char```s are any valid
+    /// Unicode scalar value.
+    pub trait Random {
+        fn random(rng: &mut Rng) -> Self;
+    }
 
-                let mut result: Vec<T> = Vec::with_capacity(len);
-                unsafe {
-                    for chunk in buffer.chunks_exact(size_of::<T>()) {
-                        let mut element: T = T::default();
-                        std::ptr::copy_nonoverlapping(
-                            chunk.as_ptr(),
-                            &mut element as *mut T as *mut u8,
-                            size_of::<T>(),
-                        );
-                        result.push(element);
+    macro_rules! impl_random_int {
+        ($($ty:ty),*) => {
+            $(
+                impl Random for $ty {
+                    fn random(rng: &mut Rng) -> Self {
+                        rng.next_u64() as $ty
                     }
                 }
-                return result;
-            }
+            )*
+        };
+    }
+
+    impl_random_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+    impl Random for u128 {
+        fn random(rng: &mut Rng) -> Self {
+            ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128
+        }
+    }
+
+    impl Random for i128 {
+        fn random(rng: &mut Rng) -> Self {
+            u128::random(rng) as i128
+        }
+    }
+
+    impl Random for bool {
+        fn random(rng: &mut Rng) -> Self {
+            rng.next_u64() >> 63 == 1
+        }
+    }
+
+    impl Random for f32 {
+        fn random(rng: &mut Rng) -> Self {
+            // Use as many bits as fit in the mantissa so every value is
+            // equally likely
+            (rng.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+        }
+    }
+
+    impl Random for f64 {
+        fn random(rng: &mut Rng) -> Self {
+            (rng.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+        }
+    }
+
+    impl Random for char {
+        fn random(rng: &mut Rng) -> Self {
+            // Skip over the surrogates, which aren't valid characters
+            const SURROGATES: u32 = 0xe000 - 0xd800;
+            const SCALAR_VALUES: u64 = 0x11_0000 - SURROGATES as u64;
+
+            let value = (((rng.next_u64() >> 32) * SCALAR_VALUES) >> 32) as u32;
+            let value = if value >= 0xd800 {
+                value + SURROGATES
+            } else {
+                value
+            };
+
+            char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER)
         }
     }
 
-    /// Generates a random number
+    /// Reseeds this thread's generator, which is used by the free functions
+    /// in this module.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::{rand, seed};
+    /// seed(1234);
+    /// let a: u32 = rand();
     ///
-    /// I have no idea if this is cryptographically secure so please don't use it
-    /// for anything like that.
+    /// seed(1234);
+    /// let b: u32 = rand();
     ///
-    /// # Examples
+    /// assert_eq!(a, b);
     /// ```
-    /// let random_char = rand::<char>();
-    /// let random_u8: u8 = rand();
+    pub fn seed(seed: u64) {
+        RNG.with(|rng| *rng.borrow_mut() = Rng::seed_from_u64(seed));
+    }
+
+    /// Runs a function with this thread's generator
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::with_rng;
+    /// let (a, b) = with_rng(|rng| (rng.rand::<u8>(), rng.rand::<u8>()));
     /// ```
-    pub fn rand<T: Default + Copy>() -> T {
+    pub fn with_rng<R>(f: impl FnOnce(&mut Rng) -> R) -> R {
+        RNG.with(|rng| f(&mut rng.borrow_mut()))
+    }
+
+    /// Reads a seed from the operating system's source of randomness
+    fn entropy() -> u64 {
+        let mut buffer = [0u8; 8];
+
         #[cfg(unix)]
         {
-            let mut buffer = vec![0u8; size_of::<T>()];
             std::fs::File::open("/dev/urandom")
                 .expect("Failed to open /dev/urandom")
                 .read_exact(&mut buffer)
                 .expect("Failed to read random bytes");
-
-            let mut result: T = T::default();
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    buffer.as_ptr(),
-                    &mut result as *mut T as *mut u8,
-                    size_of::<T>(),
-                );
-            }
-            return result;
         }
 
         #[cfg(windows)]
@@ -143,340 +232,2689 @@ pub mod rand {
                 {
                     panic!("Failed to acquire cryptographic context!");
                 }
-            }
 
-            let mut buffer = vec![0u8; std::mem::size_of::<T>()];
-            unsafe {
                 // Generate random bytes
                 if CryptGenRandom(h_provider, buffer.len() as u32, buffer.as_mut_ptr()) == 0 {
                     CryptReleaseContext(h_provider, 0);
                     panic!("Failed to generate random bytes!");
                 }
-            }
 
-            unsafe {
                 // Release the cryptographic provider context
                 CryptReleaseContext(h_provider, 0);
             }
-
-            let mut result: T = T::default();
-            unsafe {
-                // Copy the random bytes into the result
-                std::ptr::copy_nonoverlapping(
-                    buffer.as_ptr(),
-                    &mut result as *mut T as *mut u8,
-                    std::mem::size_of::<T>(),
-                );
-            }
-
-            result
         }
+
+        u64::from_ne_bytes(buffer)
     }
 
-    /// Generates a ```Vec``` of random numbers within a range using ```rand()```
+    /// Generates a ```Vec``` of random values using this thread's generator
     ///
     /// # Examples
     /// ```
-    /// let random_chars = rand_vec_range::<u8>(0, 100, 200) as char;
-    /// let random_u8s: Vec<u8> = rand_vec_range(0, 10, 25);
-    /// ```
-    pub fn vec_range<
-        T: Default
-            + Copy
-            + std::ops::Add<Output = T>
-            + std::ops::Sub<Output = T>
-            + std::ops::Rem<Output = T>
-            + PartialOrd
-            + PartialEq,
-    >(
-        min: T,
-        max: T,
-        len: usize,
-    ) -> Vec<T> {
-        if min == max {
-            return vec![min; len];
-        }
-
-        vec::<T>(len)
-            .into_iter()
-            .map(|value| {
-                // Compute the range size
-                let range_size = max - min;
-
-                // Ensure the random value is within the range
-                let result = (value % range_size) + min;
-
-                result
-            })
-            .collect()
+    /// # use a5c11::core::rand::vec;
+    /// let random_chars = vec::<char>(200);
+    /// let random_u8s: Vec<u8> = vec(25);
+    /// ```
+    pub fn vec<T: Random>(len: usize) -> Vec<T> {
+        with_rng(|rng| rng.vec(len))
     }
 
-    /// Generates a random number within a range using ```rand()```
+    /// Generates a random value using this thread's generator
+    ///
+    /// This is not cryptographically secure so please don't use it for
+    /// anything like that.
     ///
     /// # Examples
     /// ```
-    /// let random_char = rand_range::<u8>(0, 100) as char;
-    /// let random_u8: u8 = rand_range(0, 10);
+    /// # use a5c11::core::rand::rand;
+    /// let random_char = rand::<char>();
+    /// let random_u8: u8 = rand();
     /// ```
-    pub fn range<
-        T: Default
-            + Copy
-            + std::ops::Add<Output = T>
-            + std::ops::Sub<Output = T>
-            + std::ops::Rem<Output = T>
-            + PartialOrd
-            + PartialEq,
-    >(
-        min: T,
-        max: T,
-    ) -> T {
-        if min > max {
-            panic!("min must be less than or equal to max");
+    pub fn rand<T: Random>() -> T {
+        with_rng(|rng| rng.rand())
+    }
+
+    /// Types which can be sampled uniformly from a range by an ```Rng```.
+    ///
+    /// Every value in the range is equally likely, whether the bounds are
+    /// negative, positive or floating point.
+    pub trait Uniform: Copy + PartialOrd {
+        /// Samples a value between ```min``` (inclusive) and ```max```
+        /// (exclusive). Panics if ```min``` is not less than ```max```.
+        fn sample(rng: &mut Rng, min: Self, max: Self) -> Self;
+
+        /// Samples a value between ```min``` and ```max```, both inclusive.
+        /// Panics if ```min``` is greater than ```max```.
+        fn sample_inclusive(rng: &mut Rng, min: Self, max: Self) -> Self;
+    }
+
+    macro_rules! impl_uniform_int {
+        ($($ty:ty => $unsigned:ty),*) => {
+            $(
+                impl Uniform for $ty {
+                    fn sample(rng: &mut Rng, min: Self, max: Self) -> Self {
+                        assert!(min < max, "min must be less than max");
+                        Self::sample_inclusive(rng, min, max - 1)
+                    }
+
+                    fn sample_inclusive(rng: &mut Rng, min: Self, max: Self) -> Self {
+                        assert!(min <= max, "min must be less than or equal to max");
+
+                        // Wraps around to 0 when the range covers every u64
+                        let size = (max.wrapping_sub(min) as $unsigned as u64).wrapping_add(1);
+                        min.wrapping_add(rng.below(size) as $ty)
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_uniform_int!(
+        u8 => u8, u16 => u16, u32 => u32, u64 => u64, usize => usize,
+        i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize
+    );
+
+    macro_rules! impl_uniform_float {
+        ($($ty:ty => $bits:expr),*) => {
+            $(
+                impl Uniform for $ty {
+                    fn sample(rng: &mut Rng, min: Self, max: Self) -> Self {
+                        assert!(min < max, "min must be less than max");
+
+                        // Rounding can land exactly on max, so try again if it does
+                        loop {
+                            let value = min + (max - min) * rng.rand::<$ty>();
+                            if value < max {
+                                return value;
+                            }
+                        }
+                    }
+
+                    fn sample_inclusive(rng: &mut Rng, min: Self, max: Self) -> Self {
+                        assert!(min <= max, "min must be less than or equal to max");
+
+                        // Scale so that the largest possible value maps to 1.0
+                        let scale = ((1u64 << $bits) - 1) as $ty;
+                        let value = (rng.next_u64() >> (64 - $bits)) as $ty / scale;
+                        (min + (max - min) * value).clamp(min, max)
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_uniform_float!(f32 => 24, f64 => 53);
+
+    impl Rng {
+        /// Generates a value between ```min``` (inclusive) and ```max```
+        /// (exclusive). Returns ```min``` if both are equal.
+        pub fn range<T: Uniform>(&mut self, min: T, max: T) -> T {
+            if min == max {
+                return min;
+            }
+            T::sample(self, min, max)
         }
-        if min == max {
-            return min;
+
+        /// Generates a value between ```min``` and ```max```, both inclusive
+        pub fn range_inclusive<T: Uniform>(&mut self, min: T, max: T) -> T {
+            T::sample_inclusive(self, min, max)
         }
 
-        // Generate a random value using the custom `rand` function
-        let value: T = rand();
+        /// Shuffles a slice so that every order is equally likely
+        pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+            // Fisher-Yates: pick each position's element from the ones not
+            // yet placed
+            for i in (1..slice.len()).rev() {
+                let j = self.below(i as u64 + 1) as usize;
+                slice.swap(i, j);
+            }
+        }
 
-        // Compute the range size
-        let range_size = max - min;
+        /// Generates a value below ```size```, or any value if ```size```
+        /// is 0
+        fn below(&mut self, size: u64) -> u64 {
+            if size == 0 {
+                return self.next_u64();
+            }
 
-        // Ensure the random value is within the range
-        let result = (value % range_size) + min;
+            // Lemire's method: multiply into the upper half of a u128, and
+            // reject the few values which would make some results more likely
+            let mut product = self.next_u64() as u128 * size as u128;
+            if (product as u64) < size {
+                let threshold = size.wrapping_neg() % size;
+                while (product as u64) < threshold {
+                    product = self.next_u64() as u128 * size as u128;
+                }
+            }
 
-        result
+            (product >> 64) as u64
+        }
     }
 
-    /// Shuffles a vector using ```rand()```
+    /// Generates a ```Vec``` of random numbers between ```min``` (inclusive)
+    /// and ```max``` (exclusive)
     ///
-    /// # Example
+    /// # Examples
     /// ```
-    /// let mut vec: Vec<_> = (0..5).collect();
-    /// shuffle(&mut vec);
+    /// # use a5c11::core::rand::vec_range;
+    /// let random_chars: Vec<char> = vec_range::<u8>(b'a', b'z', 200)
+    ///     .into_iter()
+    ///     .map(char::from)
+    ///     .collect();
+    /// let random_f32s: Vec<f32> = vec_range(-2.0, 5.0, 25);
     /// ```
-    pub fn shuffle<
-        T: Default
-            + Copy
-            + std::ops::Sub
-            + std::ops::Add
-            + std::ops::Rem
-            + std::convert::From<<T as std::ops::Sub>::Output>
-            + std::convert::From<<T as std::ops::Add>::Output>
-            + std::convert::From<<T as std::ops::Rem>::Output>
-            + PartialEq,
-    >(
-        vec: &mut Vec<T>,
-    ) {
-        for i in 0..vec.len() {
-            let j = range(0, vec.len() - 1);
-            vec.swap(i, j);
-        }
+    pub fn vec_range<T: Uniform>(min: T, max: T, len: usize) -> Vec<T> {
+        with_rng(|rng| (0..len).map(|_| rng.range(min, max)).collect())
     }
 
-    /// Represents a Perlin noise permutation which can be used to sample
-    /// Perlin noise from.
+    /// Generates a ```Vec``` of random numbers between ```min``` and
+    /// ```max```, both inclusive
     ///
-    /// # Example
+    /// # Examples
     /// ```
-    /// let perlin = Perlin::new();
-    /// let a = perlin.sample(0.5, 1.5, 0.0, 255.0);
-    /// let b = perlin.sample(1.5, 3.5, 0.0, 255.0);
+    /// # use a5c11::core::rand::vec_range_inclusive;
+    /// let dice_rolls: Vec<u8> = vec_range_inclusive(1, 6, 10);
     /// ```
-    pub struct Perlin {
-        size: usize,
-        permutation: Vec<usize>,
+    pub fn vec_range_inclusive<T: Uniform>(min: T, max: T, len: usize) -> Vec<T> {
+        with_rng(|rng| (0..len).map(|_| rng.range_inclusive(min, max)).collect())
     }
 
-    impl Perlin {
-        /// Generate a new Perlin noise permutation which can be used to sample
-        /// from.
-        pub fn new() -> Self {
-            let size = 256;
+    /// Generates a random number between ```min``` (inclusive) and ```max```
+    /// (exclusive)
+    ///
+    /// # Examples
+    /// ```
+    /// # use a5c11::core::rand::range;
+    /// let random_char = range::<u8>(b'a', b'z') as char;
+    /// let random_i32: i32 = range(-10, 10);
+    /// let random_f32: f32 = range(-2.0, 5.0);
+    /// ```
+    pub fn range<T: Uniform>(min: T, max: T) -> T {
+        with_rng(|rng| rng.range(min, max))
+    }
 
-            let mut permutation: Vec<usize> = (0..size).collect();
-            shuffle(&mut permutation);
+    /// Generates a random number between ```min``` and ```max```, both
+    /// inclusive
+    ///
+    /// # Examples
+    /// ```
+    /// # use a5c11::core::rand::range_inclusive;
+    /// let dice_roll: u8 = range_inclusive(1, 6);
+    /// ```
+    pub fn range_inclusive<T: Uniform>(min: T, max: T) -> T {
+        with_rng(|rng| rng.range_inclusive(min, max))
+    }
 
-            Self { size, permutation }
+    /// Shuffles a slice so that every order is equally likely
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::shuffle;
+    /// let mut vec: Vec<_> = (0..5).collect();
+    /// shuffle(&mut vec);
+    /// ```
+    pub fn shuffle<T>(slice: &mut [T]) {
+        with_rng(|rng| rng.shuffle(slice))
+    }
+
+    impl Rng {
+        /// Generates ```true``` with probability ```p```
+        pub fn bernoulli(&mut self, p: f32) -> bool {
+            self.rand::<f32>() < p
         }
 
-        /// Sample Perlin noise at the specified coordinate, with the specified
-        /// miniumum and maximum values.
-        ///
-        /// # Example
-        /// ```
-        /// let a = perlin.sample(0.5, 1.5, 0.0, 255.0);
-        /// let b = perlin.sample(1.5, 3.5, 0.0, 255.0);
-        /// ```
-        pub fn sample(&self, x: f32, y: f32, min: f32, max: f32) -> f32 {
-            let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
-            let lerp = |a: f32, b: f32, t: f32| a + t * (b - a);
-            let grad = |hash: usize, x: f32, y: f32| {
-                let h = hash & 15;
-                let u = if h < 8 { x } else { y };
-                let v = if h < 4 {
-                    y
-                } else if h == 12 || h == 14 {
-                    x
-                } else {
-                    0.0
-                };
-                (if (h & 1) == 0 { u } else { -u }) + (if (h & 2) == 0 { v } else { -v })
-            };
+        /// Generates a value from a normal (Gaussian) distribution
+        pub fn normal(&mut self, mean: f32, std_dev: f32) -> f32 {
+            // Marsaglia polar method: pick a point inside the unit circle
+            loop {
+                let x = self.range_inclusive(-1.0f64, 1.0);
+                let y = self.range_inclusive(-1.0f64, 1.0);
+                let s = x * x + y * y;
+                if s > 0.0 && s < 1.0 {
+                    let z = x * (-2.0 * s.ln() / s).sqrt();
+                    return mean + std_dev * z as f32;
+                }
+            }
+        }
 
-            // Determine grid cell coordinates
-            let x_floor = x.floor() as usize & 255;
-            let y_floor = y.floor() as usize & 255;
-
-            // Relative coordinates in grid cell
-            let xf = x - x.floor();
-            let yf = y - y.floor();
-
-            // Fade curves for smooth interpolation
-            let u = fade(xf);
-            let v = fade(yf);
-
-            // Hash coordinates of the 4 corners
-            let aa = self.permutation[(x_floor + self.permutation[y_floor]) % self.size] as f32;
-            let ab = self.permutation
-                [(x_floor + self.permutation[(y_floor + 1) % self.size]) % self.size]
-                as f32;
-            let ba = self.permutation[(x_floor + 1 + self.permutation[y_floor]) % self.size] as f32;
-            let bb = self.permutation
-                [(x_floor + 1 + self.permutation[(y_floor + 1) % self.size]) % self.size]
-                as f32;
-
-            // Interpolate along x axis
-            let x1 = lerp(
-                grad(aa as usize, xf, yf),
-                grad(ba as usize, xf - 1.0, yf),
-                u,
-            );
-            let x2 = lerp(
-                grad(ab as usize, xf, yf - 1.0),
-                grad(bb as usize, xf - 1.0, yf - 1.0),
-                u,
+        /// Generates a value from an exponential distribution with the given
+        /// rate, such as the time until the next of ```lambda``` events per
+        /// second happens. Panics if ```lambda``` is not a positive finite
+        /// number.
+        pub fn exponential(&mut self, lambda: f32) -> f32 {
+            assert!(
+                lambda > 0.0 && lambda.is_finite(),
+                "lambda must be positive and finite"
             );
 
-            // Interpolate along y axis
-            let value = lerp(x1, x2, v);
-            Self::normalize(value, min, max)
+            // 1.0 - x is never 0.0, so the logarithm stays finite
+            (-(1.0 - self.rand::<f64>()).ln() / lambda as f64) as f32
         }
 
-        /// Normalize a Perlin noise value between a minimum and a maximum
-        fn normalize(value: f32, min: f32, max: f32) -> f32 {
-            // Normalize the value from [-1, 1] to [0, 1]
-            let normalized = (value + 1.0) / 2.0;
+        /// Generates a value from a Poisson distribution, such as how many
+        /// events happen in a second when ```lambda``` happen on average.
+        /// Always returns 0 when ```lambda``` is 0, and panics if it is
+        /// negative or not finite.
+        pub fn poisson(&mut self, lambda: f32) -> u32 {
+            assert!(
+                lambda >= 0.0 && lambda.is_finite(),
+                "lambda must be non-negative and finite"
+            );
 
-            // Scale the normalized value to [min, max]
-            normalized * (max - min) + min
-        }
-    }
-}
+            let lambda = lambda as f64;
+            if lambda == 0.0 {
+                return 0;
+            }
 
-/// Controls functionality of the terminal.
-///
-/// Most of the functions in this module are just abtractions over platform
-/// specific code.
-pub mod terminal {
-    use std::{io::Write, mem};
+            // Knuth's method: multiply uniform values until they drop below
+            // e^-lambda. This takes about lambda steps, so only use it for
+            // small values.
+            if lambda < 30.0 {
+                let limit = (-lambda).exp();
+                let mut count = 0;
+                let mut product = self.rand::<f64>();
+                while product > limit {
+                    count += 1;
+                    product *= self.rand::<f64>();
+                }
+                return count;
+            }
 
-    use super::Sprite;
-    #[cfg(windows)]
-    use winapi::um::{
-        consoleapi::GetConsoleMode,
-        handleapi::INVALID_HANDLE_VALUE,
-        processenv::GetStdHandle,
-        winbase::STD_OUTPUT_HANDLE,
-        wincon::{
-            GetConsoleCursorInfo, GetConsoleScreenBufferInfo, SetConsoleCursorInfo,
-            CONSOLE_CURSOR_INFO, CONSOLE_SCREEN_BUFFER_INFO, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
-        },
-        winnt::HANDLE,
-        winuser::ShowCursor,
-    };
+            // Hörmann's transformed rejection with squeeze (PTRS)
+            let sqrt_lambda = lambda.sqrt();
+            let ln_lambda = lambda.ln();
+            let b = 0.931 + 2.53 * sqrt_lambda;
+            let a = -0.059 + 0.02483 * b;
+            let inv_alpha = 1.1239 + 1.1328 / (b - 3.4);
+            let v_r = 0.9277 - 3.6224 / (b - 2.0);
+
+            loop {
+                let u = self.rand::<f64>() - 0.5;
+                let v = self.rand::<f64>();
+                let us = 0.5 - u.abs();
+                let k = ((2.0 * a / us + b) * u + lambda + 0.43).floor();
+
+                if us >= 0.07 && v <= v_r {
+                    return k as u32;
+                }
 
-    #[cfg(unix)]
-    use {
-        libc::{
-            ioctl, tcgetattr, tcsetattr, winsize, ECHO, ICANON, IXOFF, IXON, STDOUT_FILENO,
-            TCSANOW, TIOCGWINSZ, VMIN, VTIME,
-        },
-        std::os::fd::AsRawFd,
-    };
+                if k < 0.0 || (us < 0.013 && v > us) {
+                    continue;
+                }
 
-    /// Represents an escape character
-    pub const ESC: &'static str = "\x1b";
+                if v.ln() + inv_alpha.ln() - (a / (us * us) + b).ln()
+                    <= -lambda + k * ln_lambda - ln_factorial(k)
+                {
+                    return k as u32;
+                }
+            }
+        }
 
-    pub const SCREEN_BUFFER_ALT: &'static str = "\x1b[?1049h\x1b[2J\x1b[H";
-    pub const SCREEN_BUFFER_DEF: &'static str = "\x1b[2J\x1b[H\x1b[?10491";
+        /// Picks a random element from a slice, or ```None``` if it's empty
+        pub fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+            if slice.is_empty() {
+                return None;
+            }
+            slice.get(self.below(slice.len() as u64) as usize)
+        }
 
-    /// Represents an element on the screen.
-    ///
-    /// See https://gist.github.com/fnky/458719343aabd01cfb17a3a4f7296797#256-colors
-    /// for what colors are able to be used.
-    #[derive(Clone)]
-    pub struct Element {
-        char: char,
-        fg_code: u8,
-        bg_code: u8,
-    }
+        /// Picks a random element from a slice of ```(element, weight)```
+        /// pairs, where elements with a higher weight are picked more often.
+        ///
+        /// Elements with a weight of zero or less are never picked. Returns
+        /// ```None``` if no element can be picked.
+        pub fn choose_weighted<'a, T>(&mut self, items: &'a [(T, f32)]) -> Option<&'a T> {
+            let weight = |weight: f32| if weight > 0.0 { weight as f64 } else { 0.0 };
+            let total: f64 = items.iter().map(|(_, w)| weight(*w)).sum();
+            if total <= 0.0 || !total.is_finite() {
+                return None;
+            }
 
-    impl Element {
-        pub fn new(character: char, foreground_code: u8, background_code: u8) -> Self {
-            Self {
-                char: character,
-                fg_code: foreground_code,
-                bg_code: background_code,
+            let mut target = self.range(0.0, total);
+            for (item, w) in items {
+                let w = weight(*w);
+                if target < w {
+                    return Some(item);
+                }
+                target -= w;
             }
-        }
 
-        pub fn char(&self) -> char {
-            self.char
+            // Rounding can leave a tiny bit over, which belongs to the last
+            // element that can be picked
+            items
+                .iter()
+                .rev()
+                .find(|(_, w)| *w > 0.0)
+                .map(|(item, _)| item)
         }
 
-        pub fn fg(&self) -> String {
-            format!("{ESC}[38;5;{}m", self.fg_code)
-        }
+        /// Picks ```n``` different elements from a slice in a random order,
+        /// or every element if there are fewer than ```n```
+        pub fn sample_without_replacement<'a, T>(
+            &mut self,
+            slice: &'a [T],
+            n: usize,
+        ) -> Vec<&'a T> {
+            let n = n.min(slice.len());
+            let mut indices: Vec<usize> = (0..slice.len()).collect();
+
+            // Only the first n steps of a Fisher-Yates shuffle are needed
+            for i in 0..n {
+                let j = i + self.below((indices.len() - i) as u64) as usize;
+                indices.swap(i, j);
+            }
 
-        pub fn bg(&self) -> String {
-            format!("{ESC}[48;5;{}m", self.bg_code)
+            indices[..n].iter().map(|i| &slice[*i]).collect()
         }
-        // Method to reverse the foreground and background colors
-        pub fn reverse_colors(&mut self) {
-            std::mem::swap(&mut self.fg_code, &mut self.bg_code);
+    }
+
+    /// Natural logarithm of ```k!```
+    fn ln_factorial(k: f64) -> f64 {
+        if k < 10.0 {
+            return (2..=k as u64).map(|i| (i as f64).ln()).sum();
         }
+
+        // Stirling's series is accurate to well within f64 precision here
+        let k1 = k + 1.0;
+        (k1 - 0.5) * k1.ln() - k1 + 0.5 * (2.0 * std::f64::consts::PI).ln() + 1.0 / (12.0 * k1)
+            - 1.0 / (360.0 * k1.powi(3))
     }
 
-    /// Handle to hold terminal values.
+    /// Generates ```true``` with probability ```p``` using this thread's
+    /// generator
     ///
-    /// At its current state this is only used to return the terminal to its
-    /// original state once the application is closed. This is done by keeping
-    /// a copy of the previous state of the terminal and reverting to that
-    /// state when ```Handle::drop()``` is called.
-    pub struct Handle {
-        #[cfg(unix)]
-        original_termios: libc::termios,
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::bernoulli;
+    /// let critical_hit = bernoulli(0.1);
+    /// ```
+    pub fn bernoulli(p: f32) -> bool {
+        with_rng(|rng| rng.bernoulli(p))
+    }
+
+    /// Generates a value from a normal (Gaussian) distribution using this
+    /// thread's generator
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::normal;
+    /// let height = normal(170.0, 10.0);
+    /// ```
+    pub fn normal(mean: f32, std_dev: f32) -> f32 {
+        with_rng(|rng| rng.normal(mean, std_dev))
+    }
+
+    /// Generates a value from an exponential distribution using this
+    /// thread's generator. Panics if ```lambda``` is not a positive finite
+    /// number.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::exponential;
+    /// let seconds_until_next_spawn = exponential(0.5);
+    /// ```
+    pub fn exponential(lambda: f32) -> f32 {
+        with_rng(|rng| rng.exponential(lambda))
+    }
+
+    /// Generates a value from a Poisson distribution using this thread's
+    /// generator. Panics if ```lambda``` is negative or not finite.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::poisson;
+    /// let enemies_this_wave = poisson(4.0);
+    /// ```
+    pub fn poisson(lambda: f32) -> u32 {
+        with_rng(|rng| rng.poisson(lambda))
+    }
+
+    /// Picks a random element from a slice using this thread's generator
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::choose;
+    /// let greeting = choose(&["hello", "hi", "hey"]).unwrap();
+    /// ```
+    pub fn choose<T>(slice: &[T]) -> Option<&T> {
+        with_rng(|rng| rng.choose(slice))
+    }
+
+    /// Picks a random element from a slice of ```(element, weight)``` pairs
+    /// using this thread's generator
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::choose_weighted;
+    /// let loot = [("common", 70.0), ("rare", 25.0), ("legendary", 5.0)];
+    /// let drop = choose_weighted(&loot).unwrap();
+    /// ```
+    pub fn choose_weighted<T>(items: &[(T, f32)]) -> Option<&T> {
+        with_rng(|rng| rng.choose_weighted(items))
+    }
+
+    /// Picks ```n``` different elements from a slice using this thread's
+    /// generator
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::sample_without_replacement;
+    /// let deck: Vec<u8> = (1..=52).collect();
+    /// let hand = sample_without_replacement(&deck, 5);
+    /// assert_eq!(hand.len(), 5);
+    /// ```
+    pub fn sample_without_replacement<T>(slice: &[T], n: usize) -> Vec<&T> {
+        with_rng(|rng| rng.sample_without_replacement(slice, n))
+    }
+
+    /// Settings for layering octaves of noise on top of each other, each one
+    /// at a higher frequency and lower amplitude than the last.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::Octaves;
+    /// let octaves = Octaves::new(6, 2.0, 0.5);
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Octaves {
+        /// How many layers of noise are added together
+        pub count: u32,
+        /// How much the frequency is multiplied by for each octave
+        pub lacunarity: f32,
+        /// How much the amplitude is multiplied by for each octave
+        pub persistence: f32,
+    }
+
+    impl Default for Octaves {
+        fn default() -> Self {
+            Self::new(4, 2.0, 0.5)
+        }
+    }
+
+    impl Octaves {
+        pub fn new(count: u32, lacunarity: f32, persistence: f32) -> Self {
+            Self {
+                count,
+                lacunarity,
+                persistence,
+            }
+        }
+
+        /// Adds up octaves of ```noise```, with ```layer``` turning each
+        /// octave's value into what gets added. Returns the weighted average
+        /// of the layers.
+        fn layer<const N: usize>(
+            &self,
+            point: [f32; N],
+            noise: impl Fn([f32; N], f32) -> f32,
+            layer: impl Fn(f32) -> f32,
+        ) -> f32 {
+            let mut total = 0.0;
+            let mut total_amplitude = 0.0;
+            let mut frequency = 1.0;
+            let mut amplitude = 1.0;
+
+            for _ in 0..self.count.max(1) {
+                total += amplitude * layer(noise(point.map(|x| x * frequency), frequency));
+                total_amplitude += amplitude;
+                frequency *= self.lacunarity;
+                amplitude *= self.persistence;
+            }
+
+            total / total_amplitude
+        }
+    }
+
+    /// A noise generator which can be sampled in ```N``` dimensions.
+    ///
+    /// Every generator in this module implements this, so they can be
+    /// swapped for one another. Noise is sampled by passing an array with
+    /// one coordinate per dimension, and sampling it in 0 dimensions fails
+    /// to compile. A common trick is to animate 2D noise
+    /// by sampling 3D noise with time as the third coordinate.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::{Noise, Octaves, Perlin, Simplex, Worley};
+    /// fn height(noise: &impl Noise<2>, x: f32, y: f32) -> f32 {
+    ///     noise.fbm([x / 16.0, y / 16.0], Octaves::default())
+    /// }
+    ///
+    /// let hills = height(&Perlin::with_seed(1), 3.0, 4.0);
+    /// let dunes = height(&Simplex::with_seed(1), 3.0, 4.0);
+    /// let caves = height(&Worley::with_seed(1), 3.0, 4.0);
+    /// ```
+    pub trait Noise<const N: usize> {
+        /// Sample noise at a point, returning a value between -1.0 and 1.0.
+        fn noise(&self, point: [f32; N]) -> f32;
+
+        /// Sample fractal Brownian motion: octaves of noise added together,
+        /// giving more natural looking detail. Returns a value between -1.0
+        /// and 1.0.
+        fn fbm(&self, point: [f32; N], octaves: Octaves) -> f32 {
+            octaves.layer(point, |point, _| self.noise(point), |value| value)
+        }
+
+        /// Sample ridged noise, where each octave is folded so that its zero
+        /// crossings become sharp ridges, like mountain ranges or veins.
+        /// Returns a value between -1.0 and 1.0.
+        fn ridged(&self, point: [f32; N], octaves: Octaves) -> f32 {
+            let ridge = |value: f32| (1.0 - value.abs()).powi(2);
+            octaves.layer(point, |point, _| self.noise(point), ridge) * 2.0 - 1.0
+        }
+
+        /// Sample turbulence, where each octave is folded so that its zero
+        /// crossings become sharp creases, like fire or billowing smoke.
+        /// Returns a value between -1.0 and 1.0.
+        fn turbulence(&self, point: [f32; N], octaves: Octaves) -> f32 {
+            octaves.layer(point, |point, _| self.noise(point), f32::abs) * 2.0 - 1.0
+        }
+    }
+
+    /// A shuffled table used to give every cell of a grid a pseudo-random
+    /// hash, shared by the noise generators.
+    struct Permutation {
+        size: usize,
+        permutation: Vec<usize>,
+    }
+
+    impl Permutation {
+        fn new(rng: &mut Rng) -> Self {
+            let size = 256;
+
+            let mut permutation: Vec<usize> = (0..size).collect();
+            rng.shuffle(&mut permutation);
+
+            Self { size, permutation }
+        }
+
+        /// Returns the hash of a grid cell
+        fn hash<const N: usize>(&self, cell: [i64; N]) -> usize {
+            cell.iter().fold(0, |hash, coordinate| {
+                let coordinate = coordinate.rem_euclid(self.size as i64) as usize;
+                self.permutation[(hash + coordinate) % self.size]
+            })
+        }
+
+        /// Interpolates between the values ```corner``` gives each corner of
+        /// the cell containing ```point```, wrapping cells around every
+        /// ```period``` (unless it's 0). ```corner``` is given the corner's
+        /// hash and the point's offset from it.
+        fn lattice<const N: usize>(
+            &self,
+            point: [f32; N],
+            period: [usize; N],
+            corner: impl Fn(usize, &[f32; N]) -> f32,
+        ) -> f32 {
+            // Every corner of the cell is numbered with one bit per axis
+            const { assert!(N > 0 && N < usize::BITS as usize, "unsupported dimensions") };
+
+            let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+
+            // Determine grid cell coordinates, and the relative coordinates
+            // in the grid cell
+            let cell = point.map(|x| x.floor() as i64);
+            let relative: [f32; N] = std::array::from_fn(|i| point[i] - point[i].floor());
+
+            // Interpolate between every corner of the cell, weighted by how
+            // close each one is
+            let mut total = 0.0;
+            for index in 0..(1usize << N) {
+                let mut weight = 1.0;
+                let mut offset = [0.0; N];
+                let mut coordinates = [0; N];
+
+                for axis in 0..N {
+                    let bit = (index >> axis) & 1;
+
+                    coordinates[axis] = cell[axis] + bit as i64;
+                    if period[axis] > 0 {
+                        coordinates[axis] = coordinates[axis].rem_euclid(period[axis] as i64);
+                    }
+
+                    let u = fade(relative[axis]);
+                    weight *= if bit == 1 { u } else { 1.0 - u };
+                    offset[axis] = relative[axis] - bit as f32;
+                }
+
+                total += weight * corner(self.hash(coordinates), &offset);
+            }
+
+            total
+        }
+    }
+
+    /// Dot product of an offset with the gradient picked by ```hash```
+    fn gradient<const N: usize>(hash: usize, offset: &[f32; N]) -> f32 {
+        // Signs are picked with one bit of the hash per axis
+        const { assert!(N > 0 && N < usize::BITS as usize, "unsupported dimensions") };
+
+        let sign = |axis: usize, value: f32| {
+            if (hash >> axis) & 1 == 0 {
+                value
+            } else {
+                -value
+            }
+        };
+
+        // Vary the slope in one dimension, otherwise every cell would look
+        // the same
+        if N == 1 {
+            let slope = ((hash >> 1) & 7) as f32 + 1.0;
+            return sign(0, slope * offset[0]) / 8.0;
+        }
+
+        // Gradients point at the middle of an edge of the hypercube, so one
+        // of their components is 0. 2D also uses the diagonals.
+        let zero = if N == 2 {
+            (hash >> 2) % 3
+        } else {
+            (hash >> N) % N
+        };
+
+        offset
+            .iter()
+            .enumerate()
+            .filter(|(axis, _)| *axis != zero)
+            .map(|(axis, value)| sign(axis, *value))
+            .sum()
+    }
+
+    /// Normalize a noise value between a minimum and a maximum
+    fn normalize(value: f32, min: f32, max: f32) -> f32 {
+        // Normalize the value from [-1, 1] to [0, 1]
+        let normalized = (value + 1.0) / 2.0;
+
+        // Scale the normalized value to [min, max]
+        normalized * (max - min) + min
+    }
+
+    /// Represents a Perlin noise permutation which can be used to sample
+    /// Perlin noise from.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::{Noise, Octaves, Perlin};
+    /// let perlin = Perlin::with_seed(42);
+    /// let a = perlin.sample(0.5, 1.5, 0.0, 255.0);
+    /// let b = perlin.noise([1.5, 3.5, 0.25]);
+    /// let c = perlin.fbm([1.5, 3.5], Octaves::default());
+    /// ```
+    pub struct Perlin {
+        permutation: Permutation,
+    }
+
+    impl Default for Perlin {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Perlin {
+        /// Generate a new Perlin noise permutation which can be used to sample
+        /// from, using this thread's generator.
+        pub fn new() -> Self {
+            with_rng(Self::from_rng)
+        }
+
+        /// Generate a Perlin noise permutation which is always the same for
+        /// the same seed.
+        pub fn with_seed(seed: u64) -> Self {
+            Self::from_rng(&mut Rng::seed_from_u64(seed))
+        }
+
+        /// Generate a Perlin noise permutation using the given generator.
+        pub fn from_rng(rng: &mut Rng) -> Self {
+            Self {
+                permutation: Permutation::new(rng),
+            }
+        }
+
+        /// Sample 2D Perlin noise at the specified coordinate, with the
+        /// specified miniumum and maximum values.
+        ///
+        /// # Example
+        /// ```
+        /// # let perlin = a5c11::core::rand::Perlin::new();
+        /// let a = perlin.sample(0.5, 1.5, 0.0, 255.0);
+        /// let b = perlin.sample(1.5, 3.5, 0.0, 255.0);
+        /// ```
+        pub fn sample(&self, x: f32, y: f32, min: f32, max: f32) -> f32 {
+            normalize(self.noise([x, y]), min, max)
+        }
+
+        /// Sample Perlin noise which repeats every ```period``` units along
+        /// each axis, so that it can be tiled seamlessly. A period of 0
+        /// leaves that axis unchanged.
+        ///
+        /// # Example
+        /// ```
+        /// # let perlin = a5c11::core::rand::Perlin::new();
+        /// let a = perlin.periodic([0.5, 1.5], [16, 8]);
+        /// let b = perlin.periodic([16.5, 9.5], [16, 8]);
+        /// assert!((a - b).abs() < 1e-6);
+        /// ```
+        pub fn periodic<const N: usize>(&self, point: [f32; N], period: [usize; N]) -> f32 {
+            let value = self.permutation.lattice(point, period, gradient);
+            (value * Self::scale(N)).clamp(-1.0, 1.0)
+        }
+
+        /// Sample fractal Brownian motion which repeats every ```period```
+        /// units along each axis.
+        ///
+        /// The period of each octave is multiplied by the lacunarity, so it
+        /// should be a whole number for the result to tile seamlessly.
+        pub fn fbm_periodic<const N: usize>(
+            &self,
+            point: [f32; N],
+            period: [usize; N],
+            octaves: Octaves,
+        ) -> f32 {
+            octaves.layer(
+                point,
+                |point, frequency| {
+                    self.periodic(
+                        point,
+                        period.map(|period| (period as f32 * frequency).round() as usize),
+                    )
+                },
+                |value| value,
+            )
+        }
+
+        /// Roughly how much noise has to be scaled by to cover -1.0 to 1.0
+        fn scale(dimensions: usize) -> f32 {
+            match dimensions {
+                1 => 2.0,
+                2 => 1.1,
+                3 => 1.0,
+                _ => 0.85,
+            }
+        }
+    }
+
+    impl<const N: usize> Noise<N> for Perlin {
+        fn noise(&self, point: [f32; N]) -> f32 {
+            self.periodic(point, [0; N])
+        }
+    }
+
+    /// Generates simplex noise.
+    ///
+    /// Simplex noise interpolates between the corners of simplices (such as
+    /// triangles in 2D) instead of squares, which avoids the blocky,
+    /// axis-aligned artifacts Perlin noise has when viewed at large scales.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::{Noise, Simplex};
+    /// let simplex = Simplex::with_seed(42);
+    /// let a = simplex.noise([1.5, 3.5]);
+    /// ```
+    pub struct Simplex {
+        permutation: Permutation,
+    }
+
+    impl Default for Simplex {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Simplex {
+        /// Generate simplex noise using this thread's generator.
+        pub fn new() -> Self {
+            with_rng(Self::from_rng)
+        }
+
+        /// Generate simplex noise which is always the same for the same seed.
+        pub fn with_seed(seed: u64) -> Self {
+            Self::from_rng(&mut Rng::seed_from_u64(seed))
+        }
+
+        /// Generate simplex noise using the given generator.
+        pub fn from_rng(rng: &mut Rng) -> Self {
+            Self {
+                permutation: Permutation::new(rng),
+            }
+        }
+
+        /// Roughly how much noise has to be scaled by to cover -1.0 to 1.0
+        fn scale(dimensions: usize) -> f32 {
+            match dimensions {
+                1 => 70.0,
+                2 => 70.0,
+                3 => 76.0,
+                _ => 62.0,
+            }
+        }
+    }
+
+    impl<const N: usize> Noise<N> for Simplex {
+        fn noise(&self, point: [f32; N]) -> f32 {
+            let n = N as f32;
+
+            // Skew the point onto a grid of hypercubes, each of which is
+            // made up of N! simplices
+            let skew = ((n + 1.0).sqrt() - 1.0) / n;
+            let unskew = (1.0 - 1.0 / (n + 1.0).sqrt()) / n;
+
+            let offset = point.iter().sum::<f32>() * skew;
+            let cell = point.map(|x| (x + offset).floor() as i64);
+            let offset = cell.iter().sum::<i64>() as f32 * unskew;
+            let relative: [f32; N] = std::array::from_fn(|i| point[i] - (cell[i] as f32 - offset));
+
+            // The simplex containing the point is found by stepping along
+            // the axes in order of how far the point is along them
+            let mut order: [usize; N] = std::array::from_fn(|i| i);
+            order.sort_by(|a, b| relative[*b].total_cmp(&relative[*a]));
+
+            let mut total = 0.0;
+            let mut corner = cell;
+            let mut corner_offset = relative;
+            for step in 0..=N {
+                if step > 0 {
+                    let axis = order[step - 1];
+                    corner[axis] += 1;
+                    corner_offset[axis] -= 1.0;
+                }
+
+                let distance: [f32; N] =
+                    std::array::from_fn(|i| corner_offset[i] + step as f32 * unskew);
+                let falloff = 0.5 - distance.iter().map(|x| x * x).sum::<f32>();
+                if falloff > 0.0 {
+                    total += falloff.powi(4) * gradient(self.permutation.hash(corner), &distance);
+                }
+            }
+
+            (total * Self::scale(N)).clamp(-1.0, 1.0)
+        }
+    }
+
+    /// Generates value noise.
+    ///
+    /// Value noise gives every point of a grid a random value and smoothly
+    /// interpolates between them. It is cheaper than Perlin noise, but
+    /// looks blockier.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::{Noise, Value};
+    /// let value = Value::with_seed(42);
+    /// let a = value.noise([1.5, 3.5]);
+    /// ```
+    pub struct Value {
+        permutation: Permutation,
+    }
+
+    impl Default for Value {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Value {
+        /// Generate value noise using this thread's generator.
+        pub fn new() -> Self {
+            with_rng(Self::from_rng)
+        }
+
+        /// Generate value noise which is always the same for the same seed.
+        pub fn with_seed(seed: u64) -> Self {
+            Self::from_rng(&mut Rng::seed_from_u64(seed))
+        }
+
+        /// Generate value noise using the given generator.
+        pub fn from_rng(rng: &mut Rng) -> Self {
+            Self {
+                permutation: Permutation::new(rng),
+            }
+        }
+
+        /// Sample value noise which repeats every ```period``` units along
+        /// each axis, so that it can be tiled seamlessly. A period of 0
+        /// leaves that axis unchanged.
+        pub fn periodic<const N: usize>(&self, point: [f32; N], period: [usize; N]) -> f32 {
+            let max = (self.permutation.size - 1) as f32;
+            self.permutation
+                .lattice(point, period, |hash, _| hash as f32 / max * 2.0 - 1.0)
+        }
+    }
+
+    impl<const N: usize> Noise<N> for Value {
+        fn noise(&self, point: [f32; N]) -> f32 {
+            self.periodic(point, [0; N])
+        }
+    }
+
+    /// Which distance Worley noise is made from
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Feature {
+        /// Distance to the closest point, giving round cells
+        F1,
+        /// Distance to the second closest point
+        F2,
+        /// Difference between the second closest and closest distances,
+        /// giving the walls between cells
+        F2MinusF1,
+    }
+
+    /// Generates Worley (cellular) noise.
+    ///
+    /// Worley noise scatters points across space, one per grid cell, and
+    /// is made from the distances to the closest of them. This gives cell
+    /// like patterns such as caves, stones or scales.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::rand::{Feature, Noise, Worley};
+    /// let worley = Worley::with_seed(42).with_feature(Feature::F2MinusF1);
+    /// let a = worley.noise([1.5, 3.5]);
+    /// let (f1, f2) = worley.distances([1.5, 3.5]);
+    /// ```
+    pub struct Worley {
+        permutation: Permutation,
+        feature: Feature,
+    }
+
+    impl Default for Worley {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Worley {
+        /// Generate Worley noise using this thread's generator.
+        pub fn new() -> Self {
+            with_rng(Self::from_rng)
+        }
+
+        /// Generate Worley noise which is always the same for the same seed.
+        pub fn with_seed(seed: u64) -> Self {
+            Self::from_rng(&mut Rng::seed_from_u64(seed))
+        }
+
+        /// Generate Worley noise using the given generator.
+        pub fn from_rng(rng: &mut Rng) -> Self {
+            Self {
+                permutation: Permutation::new(rng),
+                feature: Feature::F1,
+            }
+        }
+
+        /// Sets which distance ```noise()``` is made from. Defaults to
+        /// ```Feature::F1```.
+        pub fn with_feature(mut self, feature: Feature) -> Self {
+            self.feature = feature;
+            self
+        }
+
+        /// Returns the distances from a point to the closest and second
+        /// closest feature points.
+        pub fn distances<const N: usize>(&self, point: [f32; N]) -> (f32, f32) {
+            // Every neighbouring cell is numbered with one base 3 digit per
+            // axis
+            const {
+                assert!(
+                    N > 0 && 3usize.checked_pow(N as u32).is_some(),
+                    "unsupported dimensions"
+                )
+            };
+
+            let cell = point.map(|x| x.floor() as i64);
+            let size = self.permutation.size;
+
+            let mut f1 = f32::INFINITY;
+            let mut f2 = f32::INFINITY;
+
+            // The closest points are always within the neighbouring cells
+            for index in 0..3usize.pow(N as u32) {
+                let mut neighbour = cell;
+                let mut remaining = index;
+                for coordinate in neighbour.iter_mut() {
+                    *coordinate += (remaining % 3) as i64 - 1;
+                    remaining /= 3;
+                }
+
+                // Place the cell's point using its hash
+                let hash = self.permutation.hash(neighbour);
+                let distance = (0..N)
+                    .map(|axis| {
+                        let jitter = self.permutation.permutation[(hash + axis + 1) % size];
+                        let feature = neighbour[axis] as f32 + (jitter as f32 + 0.5) / size as f32;
+                        (feature - point[axis]).powi(2)
+                    })
+                    .sum::<f32>()
+                    .sqrt();
+
+                if distance < f1 {
+                    f2 = f1;
+                    f1 = distance;
+                } else if distance < f2 {
+                    f2 = distance;
+                }
+            }
+
+            (f1, f2)
+        }
+    }
+
+    impl<const N: usize> Noise<N> for Worley {
+        fn noise(&self, point: [f32; N]) -> f32 {
+            let (f1, f2) = self.distances(point);
+            let distance = match self.feature {
+                Feature::F1 => f1,
+                Feature::F2 => f2 / 1.5,
+                Feature::F2MinusF1 => f2 - f1,
+            };
+
+            (distance * 2.0 - 1.0).clamp(-1.0, 1.0)
+        }
+    }
+}
+
+/// Controls functionality of the terminal.
+///
+/// Most of the functions in this module are just abtractions over platform
+/// specific code.
+pub mod terminal {
+    use std::{
+        io::Write,
+        mem,
+        sync::{
+            atomic::{AtomicBool, AtomicU8, Ordering},
+            Arc, Mutex, Once, OnceLock, TryLockError,
+        },
+        thread::{self, ThreadId},
+    };
+
+    #[cfg(windows)]
+    use winapi::um::{
+        consoleapi::GetConsoleMode,
+        handleapi::INVALID_HANDLE_VALUE,
+        processenv::GetStdHandle,
+        winbase::STD_OUTPUT_HANDLE,
+        wincon::{
+            GetConsoleCursorInfo, GetConsoleScreenBufferInfo, SetConsoleCursorInfo,
+            CONSOLE_CURSOR_INFO, CONSOLE_SCREEN_BUFFER_INFO, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+        },
+        winnt::HANDLE,
+        winuser::ShowCursor,
+    };
+
+    #[cfg(unix)]
+    use {
+        libc::{
+            ioctl, tcgetattr, tcsetattr, winsize, BRKINT, CS8, CSIZE, ECHO, ECHONL, ICANON, ICRNL,
+            IEXTEN, IGNBRK, IGNCR, INLCR, ISIG, ISTRIP, IXOFF, IXON, OPOST, PARENB, PARMRK,
+            STDOUT_FILENO, TCSANOW, TIOCGWINSZ, VMIN, VTIME,
+        },
+        std::{os::fd::AsRawFd, sync::atomic::AtomicI32},
+    };
+
+    /// Represents an escape character
+    pub const ESC: &str = "\x1b";
+
+    /// How long ```init()``` waits for the terminal to answer
+    /// ```caps::probe()```
+    const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
+
+    /// Kitty keyboard protocol flag to report keys which would otherwise be
+    /// ambiguous (such as Escape or Alt+[) as escape codes.
+    ///
+    /// See https://sw.kovidgoyal.net/kitty/keyboard-protocol/#progressive-enhancement
+    pub const KITTY_DISAMBIGUATE: u8 = 0b1;
+    /// Kitty keyboard protocol flag to report repeat and release events.
+    pub const KITTY_REPORT_EVENT_TYPES: u8 = 0b10;
+    /// Kitty keyboard protocol flag to report shifted and base layout keys.
+    pub const KITTY_REPORT_ALTERNATE_KEYS: u8 = 0b100;
+    /// Kitty keyboard protocol flag to report every key as an escape code,
+    /// including text and modifier keys.
+    pub const KITTY_REPORT_ALL_KEYS: u8 = 0b1000;
+    /// Kitty keyboard protocol flag to report the text a key produces.
+    pub const KITTY_REPORT_TEXT: u8 = 0b10000;
+    /// Kitty keyboard protocol flags which report every key going down and
+    /// up, so that ```keyboard::keys()``` reflects the keys being held,
+    /// along with the text they produce.
+    pub const KITTY_HELD_KEYS: u8 = KITTY_DISAMBIGUATE
+        | KITTY_REPORT_EVENT_TYPES
+        | KITTY_REPORT_ALTERNATE_KEYS
+        | KITTY_REPORT_ALL_KEYS
+        | KITTY_REPORT_TEXT;
+
+    /// Kitty keyboard protocol flags currently requested from the terminal.
+    static KEYBOARD_FLAGS: AtomicU8 = AtomicU8::new(0);
+
+    pub const SCREEN_BUFFER_ALT: &str = "\x1b[?1049h\x1b[2J\x1b[H";
+    pub const SCREEN_BUFFER_DEF: &str = "\x1b[2J\x1b[H\x1b[?1049l";
+
+    /// How many colors the terminal is able to show.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum ColorDepth {
+        /// No colors at all, only the terminal's default ones
+        Monochrome,
+        /// The 16 standard colors
+        Ansi16,
+        /// The 256 indexed colors
+        Indexed256,
+        /// Any 24-bit color
+        TrueColor,
+    }
+
+    /// Returns how many colors the terminal is able to show.
+    ///
+    /// Unless set with ```set_color_depth()```, this is the color depth
+    /// detected by ```caps::get()```.
+    pub fn color_depth() -> ColorDepth {
+        caps::get().color_depth
+    }
+
+    /// Sets how many colors the terminal is able to show. Colors which the
+    /// terminal can't show are replaced with the closest one it can.
+    pub fn set_color_depth(depth: ColorDepth) {
+        let mut capabilities = caps::get();
+        capabilities.color_depth = depth;
+        caps::set(capabilities);
+    }
+
+    /// Detects what the terminal is able to do.
+    ///
+    /// Capabilities are first guessed from the environment, using ```TERM```,
+    /// ```COLORTERM```, ```TERM_PROGRAM```, ```NO_COLOR``` and the locale.
+    /// ```probe()``` can then ask the terminal itself, which is what
+    /// ```init()``` does, so terminals which aren't known ahead of time still
+    /// get the best encodings they support.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::terminal::{caps, ColorDepth};
+    /// let capabilities = caps::get();
+    /// if capabilities.color_depth == ColorDepth::TrueColor {
+    ///     println!("24-bit colors are supported");
+    /// }
+    /// ```
+    pub mod caps {
+        use super::ColorDepth;
+        use std::{
+            env,
+            sync::{Mutex, OnceLock},
+            time::Duration,
+        };
+
         #[cfg(unix)]
-        _termios: libc::termios,
+        use {
+            super::ESC,
+            libc::{
+                isatty, poll, pollfd, read, tcgetattr, tcsetattr, ECHO, ICANON, POLLIN,
+                STDIN_FILENO, STDOUT_FILENO, TCSANOW,
+            },
+            std::{io::Write, time::Instant},
+        };
+
+        /// Capabilities of the terminal, as returned by ```get()```.
+        static CAPS: OnceLock<Mutex<Capabilities>> = OnceLock::new();
+
+        /// Bytes read while waiting for replies which weren't replies.
+        #[cfg(unix)]
+        static INPUT: OnceLock<Mutex<Vec<u8>>> = OnceLock::new();
+
+        /// Describes what the terminal is able to do.
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct Capabilities {
+            /// Name of the terminal, from XTVERSION, ```TERM_PROGRAM``` or ```TERM```
+            pub name: Option<String>,
+            /// How many colors the terminal is able to show
+            pub color_depth: ColorDepth,
+            /// Whether the terminal shows UTF-8 text
+            pub unicode: bool,
+            /// Whether the terminal supports the kitty keyboard protocol
+            pub kitty_keyboard: bool,
+            /// Whether the terminal can hold off drawing until a whole frame
+            /// has been written (mode 2026)
+            pub synchronized_output: bool,
+            /// Whether the terminal reports mouse events
+            pub mouse: bool,
+            /// Whether the terminal shows sixel graphics
+            pub sixel: bool,
+            /// Whether the terminal supports the kitty graphics protocol
+            pub kitty_graphics: bool,
+            /// Whether the terminal shows inline images using iTerm2's protocol
+            pub iterm_images: bool,
+            /// Whether the terminal draws curly, dotted, dashed and double
+            /// underlines, and underlines in their own color
+            pub styled_underline: bool,
+            /// Background color of the terminal, if it reported one
+            pub background: Option<(u8, u8, u8)>,
+            /// Terminal type and firmware version reported by the secondary
+            /// device attributes
+            pub device: Option<(u32, u32)>,
+        }
+
+        impl Default for Capabilities {
+            fn default() -> Self {
+                from_env()
+            }
+        }
+
+        /// Returns the capabilities of the terminal.
+        ///
+        /// These are guessed from the environment the first time this is
+        /// called, unless ```set()``` was called first.
+        pub fn get() -> Capabilities {
+            CAPS.get_or_init(|| Mutex::new(from_env()))
+                .lock()
+                .unwrap()
+                .clone()
+        }
+
+        /// Overrides the capabilities of the terminal, for example with the
+        /// ones returned by ```probe()```.
+        pub fn set(capabilities: Capabilities) {
+            let caps = CAPS.get_or_init(|| Mutex::new(capabilities.clone()));
+            *caps.lock().unwrap() = capabilities;
+        }
+
+        /// Guesses the capabilities of the terminal from environment variables.
+        pub fn from_env() -> Capabilities {
+            let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+            let term = var("TERM").unwrap_or_default();
+            let program = var("TERM_PROGRAM").unwrap_or_default();
+            let colorterm = var("COLORTERM").unwrap_or_default();
+            let locale = var("LC_ALL")
+                .or_else(|| var("LC_CTYPE"))
+                .or_else(|| var("LANG"))
+                .unwrap_or_default()
+                .to_lowercase();
+
+            let is = |names: &[&str]| {
+                names
+                    .iter()
+                    .any(|name| term.contains(name) || program.eq_ignore_ascii_case(name))
+            };
+
+            let color_depth = if var("NO_COLOR").is_some() || term == "dumb" {
+                ColorDepth::Monochrome
+            } else if colorterm == "truecolor"
+                || colorterm == "24bit"
+                || term.contains("direct")
+                || is(&[
+                    "kitty",
+                    "alacritty",
+                    "foot",
+                    "ghostty",
+                    "wezterm",
+                    "iTerm.app",
+                ])
+                || var("WT_SESSION").is_some()
+                || cfg!(windows)
+            {
+                ColorDepth::TrueColor
+            } else if term.contains("256") || program == "Apple_Terminal" {
+                ColorDepth::Indexed256
+            } else if term == "linux" || term.starts_with("vt") || term == "ansi" {
+                ColorDepth::Ansi16
+            } else {
+                ColorDepth::Indexed256
+            };
+
+            let name = match (program.is_empty(), term.is_empty()) {
+                (false, _) => Some(program.clone()),
+                (true, false) => Some(term.clone()),
+                (true, true) => None,
+            };
+
+            Capabilities {
+                name,
+                color_depth,
+                unicode: cfg!(windows)
+                    || locale.contains("utf-8")
+                    || locale.contains("utf8")
+                    || is(&["kitty", "wezterm", "ghostty"]),
+                kitty_keyboard: is(&["kitty", "foot", "ghostty", "wezterm"]),
+                synchronized_output: is(&[
+                    "kitty",
+                    "foot",
+                    "ghostty",
+                    "wezterm",
+                    "alacritty",
+                    "contour",
+                    "iTerm.app",
+                ]),
+                mouse: cfg!(windows) || !(term.is_empty() || term == "dumb" || term == "linux"),
+                sixel: is(&["foot", "mlterm", "wezterm", "contour", "iTerm.app"]),
+                kitty_graphics: is(&["kitty", "ghostty", "wezterm"]),
+                iterm_images: is(&["iTerm.app", "wezterm"]),
+                styled_underline: var("VTE_VERSION").is_some()
+                    || is(&[
+                        "kitty",
+                        "foot",
+                        "ghostty",
+                        "wezterm",
+                        "contour",
+                        "iTerm.app",
+                    ]),
+                background: None,
+                device: None,
+            }
+        }
+
+        /// Asks the terminal what it is able to do, starting from the
+        /// capabilities guessed by ```from_env()```.
+        ///
+        /// Queries are written to the terminal and its replies are read from
+        /// standard input until it answers the primary device attributes,
+        /// which every terminal does, or ```timeout``` runs out. Checking
+        /// whether the terminal shows UTF-8 writes a character at the top
+        /// left of the screen and clears the top line again, leaving the
+        /// cursor there, which ```init()``` does on the alternate screen. This has to be done before
+        /// ```keyboard::run()```, otherwise the replies get read as key
+        /// presses, and anything typed in the meantime is passed on to it.
+        /// When standard input or output isn't a terminal, nothing is
+        /// queried.
+        ///
+        /// The kitty keyboard protocol is only reported as supported when
+        /// the terminal says so, as keys would be read wrongly otherwise.
+        ///
+        /// # Example
+        /// ```no_run
+        /// # use a5c11::core::terminal::caps;
+        /// # use std::time::Duration;
+        /// caps::set(caps::probe(Duration::from_millis(100)));
+        /// ```
+        pub fn probe(timeout: Duration) -> Capabilities {
+            let mut capabilities = Capabilities {
+                kitty_keyboard: false,
+                ..from_env()
+            };
+
+            #[cfg(unix)]
+            query(
+                &format!(
+                    concat!(
+                        // Kitty keyboard protocol flags
+                        "{ESC}[?u",
+                        // Synchronized output mode
+                        "{ESC}[?2026$p",
+                        // Kitty graphics, with a query that displays nothing
+                        "{ESC}_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA{ESC}\\",
+                        // Background color
+                        "{ESC}]11;?{ESC}\\",
+                        // Whether a box drawing character moves the cursor
+                        // by one column or by its three UTF-8 bytes, on the
+                        // top line so the reply can be told apart from keys
+                        "{ESC}[H\u{2502}{ESC}[6n{ESC}[H{ESC}[2K",
+                        // XTVERSION, DA2 and finally DA1
+                        "{ESC}[>0q{ESC}[>c{ESC}[c",
+                    ),
+                    ESC = ESC
+                ),
+                timeout,
+                &mut capabilities,
+            );
+
+            #[cfg(not(unix))]
+            let _ = timeout;
+
+            capabilities
+        }
+
+        /// Asks the terminal whether it supports the kitty keyboard protocol,
+        /// without the rest of ```probe()```.
+        pub(super) fn probe_keyboard(timeout: Duration) -> bool {
+            let mut capabilities = Capabilities {
+                kitty_keyboard: false,
+                ..from_env()
+            };
+
+            #[cfg(unix)]
+            query(&format!("{ESC}[?u{ESC}[c"), timeout, &mut capabilities);
+
+            #[cfg(not(unix))]
+            let _ = timeout;
+
+            capabilities.kitty_keyboard
+        }
+
+        /// Writes ```queries```, which have to end by asking for the primary
+        /// device attributes, and updates ```capabilities``` with the
+        /// replies.
+        ///
+        /// Bytes read which aren't replies are kept for
+        /// ```take_input()```.
+        #[cfg(unix)]
+        fn query(queries: &str, timeout: Duration, capabilities: &mut Capabilities) {
+            if unsafe { isatty(STDIN_FILENO) == 0 || isatty(STDOUT_FILENO) == 0 } {
+                return;
+            }
+
+            // Replies only arrive unechoed and without waiting for a
+            // newline outside of canonical mode
+            let original_termios = unsafe {
+                let mut termios = std::mem::zeroed();
+                if tcgetattr(STDIN_FILENO, &mut termios) != 0 {
+                    return;
+                }
+                termios
+            };
+            let mut termios = original_termios;
+            termios.c_lflag &= !(ICANON | ECHO);
+            unsafe { tcsetattr(STDIN_FILENO, TCSANOW, &termios) };
+
+            let mut stdout = std::io::stdout();
+            let mut replies = Vec::new();
+            let mut input = Vec::new();
+            let cursor_query = queries.contains(&format!("{ESC}[6n"));
+
+            if stdout
+                .write_all(queries.as_bytes())
+                .and_then(|_| stdout.flush())
+                .is_ok()
+            {
+                let deadline = Instant::now() + timeout;
+                let mut buffer = [0u8; 256];
+
+                loop {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        break;
+                    }
+
+                    let mut fds = pollfd {
+                        fd: STDIN_FILENO,
+                        events: POLLIN,
+                        revents: 0,
+                    };
+                    if unsafe { poll(&mut fds, 1, remaining.as_millis().max(1) as i32) } <= 0 {
+                        break;
+                    }
+
+                    let count =
+                        unsafe { read(STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len()) };
+                    if count <= 0 {
+                        break;
+                    }
+
+                    replies.extend_from_slice(&buffer[..count as usize]);
+                    let done;
+                    (done, input) = parse_replies(&replies, cursor_query, capabilities);
+                    if done {
+                        break;
+                    }
+                }
+            }
+
+            unsafe { tcsetattr(STDIN_FILENO, TCSANOW, &original_termios) };
+
+            INPUT
+                .get_or_init(|| Mutex::new(Vec::new()))
+                .lock()
+                .unwrap()
+                .extend(input);
+        }
+
+        /// Returns the bytes read by ```probe()``` which weren't replies to
+        /// its queries, such as keys typed while waiting for them.
+        #[cfg(target_os = "linux")]
+        pub(crate) fn take_input() -> Vec<u8> {
+            INPUT
+                .get()
+                .map(|input| std::mem::take(&mut *input.lock().unwrap()))
+                .unwrap_or_default()
+        }
+
+        /// Updates ```capabilities``` with the replies to the queries written
+        /// by ```probe()```. Returns whether the primary device attributes,
+        /// which are always answered last, were among them, and the bytes
+        /// which weren't replies.
+        ///
+        /// A cursor position reply looks like a key with modifiers, so one is
+        /// only taken as a reply when ```cursor_query``` says the cursor
+        /// position was asked for, and only once.
+        fn parse_replies(
+            bytes: &[u8],
+            cursor_query: bool,
+            capabilities: &mut Capabilities,
+        ) -> (bool, Vec<u8>) {
+            let mut done = false;
+            let mut cursor_pending = cursor_query;
+            let mut input = Vec::new();
+            let mut i = 0;
+
+            while i < bytes.len() {
+                if bytes[i] != 0x1b || i + 1 == bytes.len() {
+                    input.push(bytes[i]);
+                    i += 1;
+                    continue;
+                }
+
+                match bytes[i + 1] {
+                    b'[' => {
+                        let start = i + 2;
+                        let Some(end) = bytes[start..]
+                            .iter()
+                            .position(|byte| (0x40..=0x7e).contains(byte))
+                        else {
+                            input.extend_from_slice(&bytes[i..]);
+                            break;
+                        };
+
+                        let body = &bytes[start..start + end];
+                        let last = bytes[start + end];
+                        if !csi_reply(body, last, capabilities, &mut cursor_pending, &mut done) {
+                            input.extend_from_slice(&bytes[i..start + end + 1]);
+                        }
+                        i = start + end + 1;
+                    }
+                    kind @ (b']' | b'P' | b'_') => {
+                        // Strings end with ST, or BEL for OSC
+                        let start = i + 2;
+                        let Some(end) = (start..bytes.len()).find(|&j| {
+                            bytes[j] == 0x07
+                                || (bytes[j] == 0x1b && bytes.get(j + 1) == Some(&b'\\'))
+                        }) else {
+                            input.extend_from_slice(&bytes[i..]);
+                            break;
+                        };
+
+                        let body = String::from_utf8_lossy(&bytes[start..end]);
+                        string_reply(kind, &body, capabilities);
+                        i = end + if bytes[end] == 0x07 { 1 } else { 2 };
+                    }
+                    _ => {
+                        input.push(bytes[i]);
+                        i += 1;
+                    }
+                }
+            }
+
+            (done, input)
+        }
+
+        /// Handles a control sequence, returning whether it was a reply
+        fn csi_reply(
+            body: &[u8],
+            last: u8,
+            capabilities: &mut Capabilities,
+            cursor_pending: &mut bool,
+            done: &mut bool,
+        ) -> bool {
+            let body = String::from_utf8_lossy(body);
+            let (marker, params) = match body.chars().next() {
+                Some(marker @ ('?' | '>')) => (Some(marker), &body[1..]),
+                _ => (None, &body[..]),
+            };
+            let params: Vec<u32> = params
+                .trim_end_matches('$')
+                .split(';')
+                .map(|param| param.parse().unwrap_or(0))
+                .collect();
+
+            match (marker, last) {
+                // Primary device attributes, where 4 means sixel graphics
+                (Some('?'), b'c') => {
+                    capabilities.sixel |= params.iter().skip(1).any(|param| *param == 4);
+                    *done = true;
+                }
+                // Secondary device attributes
+                (Some('>'), b'c') => {
+                    capabilities.device =
+                        Some((params[0], params.get(1).copied().unwrap_or_default()))
+                }
+                (Some('?'), b'u') => capabilities.kitty_keyboard = true,
+                (Some('?'), b'y') if params[0] == 2026 => {
+                    capabilities.synchronized_output = matches!(params.get(1), Some(1 | 2))
+                }
+                // Cursor position after writing a single box drawing
+                // character on the top line. Keys like Ctrl+F3 are sent the
+                // same way, but with the modifiers where the column is
+                (None, b'R') if *cursor_pending && params.len() == 2 && params[0] == 1 => {
+                    *cursor_pending = false;
+                    capabilities.unicode = params[1] == 2;
+                }
+                // Anything else with a marker is a reply nothing asked for
+                (Some(_), _) => {}
+                _ => return false,
+            }
+
+            true
+        }
+
+        /// Handles a reply which is an OSC, DCS or APC string
+        fn string_reply(kind: u8, body: &str, capabilities: &mut Capabilities) {
+            match kind {
+                b']' => {
+                    if let Some(color) = body.strip_prefix("11;rgb:") {
+                        let channels: Vec<u8> = color
+                            .split('/')
+                            .filter_map(|channel| {
+                                let value = u32::from_str_radix(channel, 16).ok()?;
+                                let max = (1u32 << (4 * channel.len().min(4))) - 1;
+                                Some((value * 255 / max) as u8)
+                            })
+                            .collect();
+
+                        if let [r, g, b] = channels[..] {
+                            capabilities.background = Some((r, g, b));
+                        }
+                    }
+                }
+                b'P' => {
+                    if let Some(name) = body.strip_prefix(">|") {
+                        capabilities.name = Some(name.to_string());
+                    }
+                }
+                b'_' if body.starts_with("Gi=31;OK") => capabilities.kitty_graphics = true,
+                _ => {}
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+
+            fn parse(bytes: &[u8], cursor_query: bool) -> (bool, Vec<u8>, bool) {
+                let mut capabilities = Capabilities {
+                    unicode: false,
+                    ..from_env()
+                };
+                let (done, input) = parse_replies(bytes, cursor_query, &mut capabilities);
+                (done, input, capabilities.unicode)
+            }
+
+            #[test]
+            fn cursor_position_reply() {
+                assert_eq!(parse(b"\x1b[1;2R\x1b[?62c", true), (true, Vec::new(), true));
+            }
+
+            #[test]
+            fn modified_f3_is_input() {
+                // Ctrl+F3 while nothing asked for the cursor position
+                assert_eq!(
+                    parse(b"\x1b[1;5R\x1b[?62c", false),
+                    (true, b"\x1b[1;5R".to_vec(), false)
+                );
+
+                // Only the first reply is taken, and only from the top line
+                assert_eq!(
+                    parse(b"x\x1b[3;2R\x1b[1;2R\x1b[1;5R\x1b[1;2;3R", true),
+                    (false, b"x\x1b[3;2R\x1b[1;5R\x1b[1;2;3R".to_vec(), true)
+                );
+            }
+        }
+    }
+
+    /// RGB values of the 16 standard colors, as xterm shows them
+    const ANSI16_PALETTE: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+
+    /// Channel values of the 6x6x6 color cube in the 256 indexed colors
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    /// Represents a color an element can be drawn with.
+    ///
+    /// Colors are converted to the closest one the terminal can show when
+    /// they are drawn (see ```color_depth()```).
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::terminal::{Color, ColorDepth};
+    /// let orange = Color::Rgb(255, 128, 0);
+    /// assert_eq!(orange.downgrade(ColorDepth::Indexed256), Color::Indexed(208));
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Color {
+        /// The terminal's own foreground or background color
+        #[default]
+        Default,
+        /// One of the 16 standard colors, 0-7 normal and 8-15 bright. Only
+        /// the lowest 4 bits are used, so 16 is the same as 0.
+        Ansi16(u8),
+        /// One of the 256 indexed colors
+        ///
+        /// See https://gist.github.com/fnky/458719343aabd01cfb17a3a4f7296797#256-colors
+        /// for what colors are able to be used.
+        Indexed(u8),
+        /// A 24-bit color
+        Rgb(u8, u8, u8),
+    }
+
+    impl From<u8> for Color {
+        fn from(code: u8) -> Self {
+            Self::Indexed(code)
+        }
+    }
+
+    impl Color {
+        /// Returns the RGB value of the color, or ```None``` for the
+        /// terminal's default color.
+        pub fn to_rgb(self) -> Option<(u8, u8, u8)> {
+            match self {
+                Self::Default => None,
+                Self::Ansi16(code) => Some(ANSI16_PALETTE[code as usize & 15]),
+                Self::Indexed(code @ 0..=15) => Some(ANSI16_PALETTE[code as usize]),
+                Self::Indexed(code @ 16..=231) => {
+                    let code = code - 16;
+                    Some((
+                        CUBE_LEVELS[(code / 36) as usize],
+                        CUBE_LEVELS[(code / 6 % 6) as usize],
+                        CUBE_LEVELS[(code % 6) as usize],
+                    ))
+                }
+                Self::Indexed(code) => {
+                    let grey = 8 + (code - 232) * 10;
+                    Some((grey, grey, grey))
+                }
+                Self::Rgb(r, g, b) => Some((r, g, b)),
+            }
+        }
+
+        /// Converts the color to the closest one a terminal with the given
+        /// color depth is able to show.
+        pub fn downgrade(self, depth: ColorDepth) -> Self {
+            match (self, depth) {
+                (_, ColorDepth::Monochrome) => Self::Default,
+                (Self::Default | Self::Ansi16(_), _) => self,
+                (Self::Indexed(code @ 0..=15), ColorDepth::Ansi16) => Self::Ansi16(code),
+                (Self::Indexed(_), ColorDepth::Indexed256 | ColorDepth::TrueColor) => self,
+                (Self::Rgb(..), ColorDepth::TrueColor) => self,
+                (Self::Rgb(r, g, b), ColorDepth::Indexed256) => {
+                    Self::Indexed(nearest_indexed(r, g, b))
+                }
+                (_, ColorDepth::Ansi16) => {
+                    let (r, g, b) = self.to_rgb().unwrap_or_default();
+                    Self::Ansi16(nearest(ANSI16_PALETTE.iter().copied(), (r, g, b)) as u8)
+                }
+            }
+        }
+
+        /// Returns the escape code which sets this as the foreground color
+        pub fn fg(self) -> String {
+            self.sgr(false, color_depth())
+        }
+
+        /// Returns the escape code which sets this as the background color
+        pub fn bg(self) -> String {
+            self.sgr(true, color_depth())
+        }
+
+        /// Escape code which sets this as the foreground or background color
+        fn sgr(self, background: bool, depth: ColorDepth) -> String {
+            let layer = if background { 10 } else { 0 };
+
+            match self.downgrade(depth) {
+                Self::Default => format!("{ESC}[{}m", 39 + layer),
+                Self::Ansi16(code) => match code & 15 {
+                    code @ 0..=7 => format!("{ESC}[{}m", 30 + layer + code),
+                    code => format!("{ESC}[{}m", 90 + layer + code - 8),
+                },
+                Self::Indexed(code) => format!("{ESC}[{};5;{code}m", 38 + layer),
+                Self::Rgb(r, g, b) => format!("{ESC}[{};2;{r};{g};{b}m", 38 + layer),
+            }
+        }
+
+        /// SGR parameter which sets this as the underline color
+        fn underline_param(self, depth: ColorDepth) -> String {
+            match self.downgrade(depth) {
+                Self::Default => "59".to_string(),
+                Self::Ansi16(code) => format!("58:5:{}", code & 15),
+                Self::Indexed(code) => format!("58:5:{code}"),
+                Self::Rgb(r, g, b) => format!("58:2::{r}:{g}:{b}"),
+            }
+        }
+    }
+
+    /// Returns the index of the color closest to ```target```
+    fn nearest(colors: impl Iterator<Item = (u8, u8, u8)>, target: (u8, u8, u8)) -> usize {
+        // Weight the channels roughly by how sensitive eyes are to them
+        let distance = |(r, g, b): (u8, u8, u8)| {
+            let dr = r as i32 - target.0 as i32;
+            let dg = g as i32 - target.1 as i32;
+            let db = b as i32 - target.2 as i32;
+            3 * dr * dr + 4 * dg * dg + 2 * db * db
+        };
+
+        colors
+            .enumerate()
+            .min_by_key(|(_, color)| distance(*color))
+            .map(|(index, _)| index)
+            .unwrap_or_default()
+    }
+
+    /// Returns the indexed color closest to an RGB color, out of the color
+    /// cube and the grey ramp
+    fn nearest_indexed(r: u8, g: u8, b: u8) -> u8 {
+        let level = |value: u8| {
+            nearest(
+                CUBE_LEVELS.iter().map(|level| (*level, 0, 0)),
+                (value, 0, 0),
+            )
+        };
+        let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+
+        let average = (r as u32 + g as u32 + b as u32) / 3;
+        let grey = 232 + (average.saturating_sub(3) / 10).min(23) as usize;
+
+        let candidates =
+            [cube, grey].map(|code| Color::Indexed(code as u8).to_rgb().unwrap_or_default());
+        if nearest(candidates.into_iter(), (r, g, b)) == 0 {
+            cube as u8
+        } else {
+            grey as u8
+        }
+    }
+
+    /// Set of text attributes an element can be drawn with.
+    ///
+    /// Attributes are combined with ```|```. The underline styles imply
+    /// ```UNDERLINE```, and fall back to a plain underline on terminals which
+    /// don't support them (see ```caps::Capabilities::styled_underline```).
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::terminal::{Attributes, Element};
+    /// let title = Element::new('A', 15, 0).with_attributes(Attributes::BOLD | Attributes::ITALIC);
+    /// assert!(title.attributes().contains(Attributes::BOLD));
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+    pub struct Attributes(u16);
+
+    impl Attributes {
+        pub const NONE: Self = Self(0);
+        pub const BOLD: Self = Self(0b1);
+        pub const DIM: Self = Self(0b10);
+        pub const ITALIC: Self = Self(0b100);
+        pub const UNDERLINE: Self = Self(0b1000);
+        pub const DOUBLE_UNDERLINE: Self = Self(0b1_0000);
+        pub const CURLY_UNDERLINE: Self = Self(0b10_0000);
+        pub const DOTTED_UNDERLINE: Self = Self(0b100_0000);
+        pub const DASHED_UNDERLINE: Self = Self(0b1000_0000);
+        pub const BLINK: Self = Self(0b1_0000_0000);
+        pub const REVERSE: Self = Self(0b10_0000_0000);
+        pub const STRIKETHROUGH: Self = Self(0b100_0000_0000);
+
+        /// Attributes which are turned on and off with a single code, along
+        /// with those codes
+        const TOGGLES: [(Self, u8, u8); 4] = [
+            (Self::ITALIC, 3, 23),
+            (Self::BLINK, 5, 25),
+            (Self::REVERSE, 7, 27),
+            (Self::STRIKETHROUGH, 9, 29),
+        ];
+
+        /// Returns the raw bits of the attributes
+        pub const fn bits(self) -> u16 {
+            self.0
+        }
+
+        pub const fn is_empty(self) -> bool {
+            self.0 == 0
+        }
+
+        /// Returns whether every attribute in ```other``` is set
+        pub const fn contains(self, other: Self) -> bool {
+            self.0 & other.0 == other.0
+        }
+
+        pub fn insert(&mut self, other: Self) {
+            self.0 |= other.0;
+        }
+
+        pub fn remove(&mut self, other: Self) {
+            self.0 &= !other.0;
+        }
+
+        /// Returns the underline style as used by ```4:n```, or ```None```
+        /// if the text isn't underlined
+        fn underline(self) -> Option<u8> {
+            [
+                (Self::CURLY_UNDERLINE, 3),
+                (Self::DOTTED_UNDERLINE, 4),
+                (Self::DASHED_UNDERLINE, 5),
+                (Self::DOUBLE_UNDERLINE, 2),
+                (Self::UNDERLINE, 1),
+            ]
+            .into_iter()
+            .find(|(attribute, _)| self.contains(*attribute))
+            .map(|(_, style)| style)
+        }
+
+        /// Returns the SGR parameters which change the attributes from
+        /// ```self``` to ```to```, or an empty list if they are the same
+        fn transition(self, to: Self, styled_underline: bool) -> Vec<String> {
+            let mut params = Vec::new();
+
+            // Bold and dim are both turned off by the same code
+            let intensity = Self::BOLD | Self::DIM;
+            if self.0 & intensity.0 != to.0 & intensity.0 {
+                let reset = self.0 & intensity.0 & !to.0 != 0;
+                if reset {
+                    params.push("22".to_string());
+                }
+                for (attribute, code) in [(Self::BOLD, "1"), (Self::DIM, "2")] {
+                    let kept = self.contains(attribute) && !reset;
+                    if to.contains(attribute) && !kept {
+                        params.push(code.to_string());
+                    }
+                }
+            }
+
+            let (from_underline, to_underline) = if styled_underline {
+                (self.underline(), to.underline())
+            } else {
+                (self.underline().map(|_| 1), to.underline().map(|_| 1))
+            };
+            match (from_underline == to_underline, to_underline) {
+                (true, _) => {}
+                (false, None) => params.push("24".to_string()),
+                (false, Some(1)) => params.push("4".to_string()),
+                (false, Some(style)) => params.push(format!("4:{style}")),
+            }
+
+            for (attribute, on, off) in Self::TOGGLES {
+                match (self.contains(attribute), to.contains(attribute)) {
+                    (false, true) => params.push(on.to_string()),
+                    (true, false) => params.push(off.to_string()),
+                    _ => {}
+                }
+            }
+
+            params
+        }
+    }
+
+    impl std::ops::BitOr for Attributes {
+        type Output = Self;
+
+        fn bitor(self, other: Self) -> Self {
+            Self(self.0 | other.0)
+        }
+    }
+
+    impl std::ops::BitOrAssign for Attributes {
+        fn bitor_assign(&mut self, other: Self) {
+            self.0 |= other.0;
+        }
+    }
+
+    impl std::ops::BitAnd for Attributes {
+        type Output = Self;
+
+        fn bitand(self, other: Self) -> Self {
+            Self(self.0 & other.0)
+        }
+    }
+
+    impl std::ops::Not for Attributes {
+        type Output = Self;
+
+        fn not(self) -> Self {
+            Self(!self.0)
+        }
+    }
+
+    /// Splits text into grapheme clusters and measures how many terminal
+    /// columns they take up.
+    ///
+    /// The tables are a hand-rolled approximation of the Unicode East Asian
+    /// Width property and grapheme cluster rules, covering combining marks,
+    /// CJK, Hangul and emoji sequences, which is what terminals themselves
+    /// mostly go by.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::terminal::unicode::{graphemes, width};
+    /// assert_eq!(graphemes("e\u{301}日"), vec!["e\u{301}", "日"]);
+    /// assert_eq!(width("e\u{301}日"), 3);
+    /// ```
+    pub mod unicode {
+        /// Characters which take up two columns
+        const WIDE: &[(u32, u32)] = &[
+            (0x1100, 0x115F),
+            (0x231A, 0x231B),
+            (0x2329, 0x232A),
+            (0x23E9, 0x23EC),
+            (0x23F0, 0x23F0),
+            (0x23F3, 0x23F3),
+            (0x25FD, 0x25FE),
+            (0x2614, 0x2615),
+            (0x2648, 0x2653),
+            (0x267F, 0x267F),
+            (0x2693, 0x2693),
+            (0x26A1, 0x26A1),
+            (0x26AA, 0x26AB),
+            (0x26BD, 0x26BE),
+            (0x26C4, 0x26C5),
+            (0x26CE, 0x26CE),
+            (0x26D4, 0x26D4),
+            (0x26EA, 0x26EA),
+            (0x26F2, 0x26F3),
+            (0x26F5, 0x26F5),
+            (0x26FA, 0x26FA),
+            (0x26FD, 0x26FD),
+            (0x2705, 0x2705),
+            (0x270A, 0x270B),
+            (0x2728, 0x2728),
+            (0x274C, 0x274C),
+            (0x274E, 0x274E),
+            (0x2753, 0x2755),
+            (0x2757, 0x2757),
+            (0x2795, 0x2797),
+            (0x27B0, 0x27B0),
+            (0x27BF, 0x27BF),
+            (0x2B1B, 0x2B1C),
+            (0x2B50, 0x2B50),
+            (0x2B55, 0x2B55),
+            (0x2E80, 0x303E),
+            (0x3041, 0x33FF),
+            (0x3400, 0x4DBF),
+            (0x4E00, 0x9FFF),
+            (0xA000, 0xA4CF),
+            (0xA960, 0xA97F),
+            (0xAC00, 0xD7A3),
+            (0xF900, 0xFAFF),
+            (0xFE10, 0xFE19),
+            (0xFE30, 0xFE6F),
+            (0xFF00, 0xFF60),
+            (0xFFE0, 0xFFE6),
+            (0x16FE0, 0x16FE4),
+            (0x17000, 0x18AFF),
+            (0x1B000, 0x1B2FF),
+            (0x1F004, 0x1F004),
+            (0x1F0CF, 0x1F0CF),
+            (0x1F18E, 0x1F18E),
+            (0x1F191, 0x1F19A),
+            (0x1F200, 0x1F202),
+            (0x1F210, 0x1F23B),
+            (0x1F240, 0x1F248),
+            (0x1F250, 0x1F251),
+            (0x1F260, 0x1F265),
+            (0x1F300, 0x1F320),
+            (0x1F32D, 0x1F335),
+            (0x1F337, 0x1F37C),
+            (0x1F37E, 0x1F393),
+            (0x1F3A0, 0x1F3CA),
+            (0x1F3CF, 0x1F3D3),
+            (0x1F3E0, 0x1F3F0),
+            (0x1F3F4, 0x1F3F4),
+            (0x1F3F8, 0x1F43E),
+            (0x1F440, 0x1F440),
+            (0x1F442, 0x1F4FC),
+            (0x1F4FF, 0x1F53D),
+            (0x1F54B, 0x1F54E),
+            (0x1F550, 0x1F567),
+            (0x1F57A, 0x1F57A),
+            (0x1F595, 0x1F596),
+            (0x1F5A4, 0x1F5A4),
+            (0x1F5FB, 0x1F64F),
+            (0x1F680, 0x1F6C5),
+            (0x1F6CC, 0x1F6CC),
+            (0x1F6D0, 0x1F6D2),
+            (0x1F6D5, 0x1F6D7),
+            (0x1F6DC, 0x1F6DF),
+            (0x1F6EB, 0x1F6EC),
+            (0x1F6F4, 0x1F6FC),
+            (0x1F7E0, 0x1F7EB),
+            (0x1F7F0, 0x1F7F0),
+            (0x1F90C, 0x1F93A),
+            (0x1F93C, 0x1F945),
+            (0x1F947, 0x1F9FF),
+            (0x1FA70, 0x1FAFF),
+            (0x20000, 0x2FFFD),
+            (0x30000, 0x3FFFD),
+        ];
+
+        /// Characters which take up no columns and attach to the character
+        /// before them, such as combining marks and variation selectors
+        const ZERO_WIDTH: &[(u32, u32)] = &[
+            (0x0300, 0x036F),
+            (0x0483, 0x0489),
+            (0x0591, 0x05BD),
+            (0x05BF, 0x05BF),
+            (0x05C1, 0x05C2),
+            (0x05C4, 0x05C5),
+            (0x05C7, 0x05C7),
+            (0x0610, 0x061A),
+            (0x064B, 0x065F),
+            (0x0670, 0x0670),
+            (0x06D6, 0x06DC),
+            (0x06DF, 0x06E4),
+            (0x06E7, 0x06E8),
+            (0x06EA, 0x06ED),
+            (0x0711, 0x0711),
+            (0x0730, 0x074A),
+            (0x07A6, 0x07B0),
+            (0x0900, 0x0902),
+            (0x093A, 0x093A),
+            (0x093C, 0x093C),
+            (0x0941, 0x0948),
+            (0x094D, 0x094D),
+            (0x0951, 0x0957),
+            (0x0962, 0x0963),
+            (0x0981, 0x0981),
+            (0x09BC, 0x09BC),
+            (0x09C1, 0x09C4),
+            (0x09CD, 0x09CD),
+            (0x0E31, 0x0E31),
+            (0x0E34, 0x0E3A),
+            (0x0E47, 0x0E4E),
+            (0x1160, 0x11FF),
+            (0x1AB0, 0x1AFF),
+            (0x1DC0, 0x1DFF),
+            (0x200B, 0x200D),
+            (0x20D0, 0x20FF),
+            (0x2060, 0x2064),
+            (0x302A, 0x302D),
+            (0x3099, 0x309A),
+            (0xFE00, 0xFE0F),
+            (0xFE20, 0xFE2F),
+            (0xFEFF, 0xFEFF),
+            (0x1F3FB, 0x1F3FF),
+            (0xE0001, 0xE0001),
+            (0xE0020, 0xE007F),
+            (0xE0100, 0xE01EF),
+        ];
+
+        /// Joins the characters on either side of it into one grapheme
+        const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+        /// Asks for the character before it to be shown as an emoji
+        const EMOJI_PRESENTATION: char = '\u{FE0F}';
+
+        fn in_table(table: &[(u32, u32)], character: char) -> bool {
+            let code = character as u32;
+            table
+                .binary_search_by(|&(start, end)| {
+                    if end < code {
+                        std::cmp::Ordering::Less
+                    } else if start > code {
+                        std::cmp::Ordering::Greater
+                    } else {
+                        std::cmp::Ordering::Equal
+                    }
+                })
+                .is_ok()
+        }
+
+        fn is_regional_indicator(character: char) -> bool {
+            ('\u{1F1E6}'..='\u{1F1FF}').contains(&character)
+        }
+
+        /// Returns how many columns a single character takes up
+        pub fn char_width(character: char) -> usize {
+            if character.is_control() || in_table(ZERO_WIDTH, character) {
+                0
+            } else if in_table(WIDE, character) {
+                2
+            } else {
+                1
+            }
+        }
+
+        /// Splits text into grapheme clusters, the characters a reader
+        /// would count as one.
+        pub fn graphemes(text: &str) -> Vec<&str> {
+            let mut graphemes = Vec::new();
+            let mut start = 0;
+            let mut previous: Option<char> = None;
+            let mut regional_indicators = 0;
+
+            for (index, character) in text.char_indices() {
+                let joins = match previous {
+                    None => false,
+                    Some(previous) if previous.is_control() || character.is_control() => false,
+                    Some(ZERO_WIDTH_JOINER) => true,
+                    // Flags are pairs of regional indicators
+                    Some(previous) if is_regional_indicator(previous) => {
+                        (is_regional_indicator(character) && regional_indicators % 2 == 1)
+                            || in_table(ZERO_WIDTH, character)
+                    }
+                    Some(_) => in_table(ZERO_WIDTH, character),
+                };
+
+                if !joins && index > 0 {
+                    graphemes.push(&text[start..index]);
+                    start = index;
+                    regional_indicators = 0;
+                }
+
+                if is_regional_indicator(character) {
+                    regional_indicators += 1;
+                }
+                previous = Some(character);
+            }
+
+            if start < text.len() {
+                graphemes.push(&text[start..]);
+            }
+
+            graphemes
+        }
+
+        /// Returns how many columns a single grapheme cluster takes up.
+        pub fn grapheme_width(grapheme: &str) -> usize {
+            let mut characters = grapheme.chars();
+            let Some(first) = characters.next() else {
+                return 0;
+            };
+
+            // Emoji sequences and flags are shown as a single wide emoji
+            if is_regional_indicator(first)
+                || grapheme.contains(EMOJI_PRESENTATION)
+                || grapheme.contains(ZERO_WIDTH_JOINER) && char_width(first) == 2
+            {
+                return 2;
+            }
+
+            char_width(first)
+        }
+
+        /// Returns how many columns text takes up.
+        pub fn width(text: &str) -> usize {
+            graphemes(text).into_iter().map(grapheme_width).sum()
+        }
+    }
+
+    /// Represents an element on the screen.
+    ///
+    /// Colors can be given as a ```Color```, or as a ```u8``` to use one of
+    /// the 256 indexed colors. Text attributes and the underline color are
+    /// set with ```with_attributes()``` and ```with_underline_color()```.
+    ///
+    /// An element holds a whole grapheme cluster, so that combining marks
+    /// and emoji sequences stay together. Graphemes which are two columns
+    /// wide, like CJK and most emoji, are followed by a continuation element
+    /// covering their second column (see ```Element::text()```).
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::core::terminal::{rgb, Color, Element};
+    /// let indexed = Element::new('#', rgb(5, 0, 0), 0);
+    /// let true_color = Element::new('#', Color::Rgb(255, 128, 0), Color::Default);
+    ///
+    /// let greeting = Element::text("こんにちは", 15, 0);
+    /// assert_eq!(greeting.len(), 10);
+    /// assert!(greeting[1].is_continuation());
+    /// ```
+    #[derive(Clone, PartialEq)]
+    pub struct Element {
+        /// The grapheme cluster shown, or empty for a continuation element
+        grapheme: String,
+        fg_color: Color,
+        bg_color: Color,
+        attributes: Attributes,
+        underline_color: Color,
+    }
+
+    impl Element {
+        pub fn new(
+            character: char,
+            foreground: impl Into<Color>,
+            background: impl Into<Color>,
+        ) -> Self {
+            Self {
+                grapheme: character.to_string(),
+                fg_color: foreground.into(),
+                bg_color: background.into(),
+                attributes: Attributes::NONE,
+                underline_color: Color::Default,
+            }
+        }
+
+        /// Creates an element showing a grapheme cluster. Only the first
+        /// grapheme cluster of ```grapheme``` is kept.
+        pub fn from_grapheme(
+            grapheme: &str,
+            foreground: impl Into<Color>,
+            background: impl Into<Color>,
+        ) -> Self {
+            let grapheme = unicode::graphemes(grapheme).first().copied().unwrap_or(" ");
+            let mut element = Self::new(' ', foreground, background);
+            element.grapheme = grapheme.to_string();
+            element
+        }
+
+        /// Creates a row of elements showing text, with a continuation
+        /// element after each wide grapheme.
+        pub fn text(
+            text: &str,
+            foreground: impl Into<Color>,
+            background: impl Into<Color>,
+        ) -> Vec<Self> {
+            let blank = Self::new(' ', foreground, background);
+            let mut elements = Vec::with_capacity(text.len());
+
+            for grapheme in unicode::graphemes(text) {
+                let mut element = blank.clone();
+                element.grapheme = grapheme.to_string();
+
+                let wide = element.width() == 2;
+                elements.push(element);
+                if wide {
+                    elements.push(blank.continuation());
+                }
+            }
+
+            elements
+        }
+
+        /// Returns a continuation element with the same colors and
+        /// attributes, which covers the second column of this one.
+        pub fn continuation(&self) -> Self {
+            Self {
+                grapheme: String::new(),
+                ..self.clone()
+            }
+        }
+
+        /// Returns the element with the given text attributes
+        pub fn with_attributes(mut self, attributes: Attributes) -> Self {
+            self.attributes = attributes;
+            self
+        }
+
+        /// Returns the element with the given underline color. Terminals
+        /// which don't support styled underlines use the foreground color.
+        pub fn with_underline_color(mut self, color: impl Into<Color>) -> Self {
+            self.underline_color = color.into();
+            self
+        }
+
+        /// Returns the first character of the grapheme, or a space for a
+        /// continuation element
+        pub fn char(&self) -> char {
+            self.grapheme.chars().next().unwrap_or(' ')
+        }
+
+        pub fn grapheme(&self) -> &str {
+            &self.grapheme
+        }
+
+        /// Returns whether this element covers the second column of the wide
+        /// grapheme before it
+        pub fn is_continuation(&self) -> bool {
+            self.grapheme.is_empty()
+        }
+
+        /// Returns how many columns the element takes up: 2 for wide
+        /// graphemes, 0 for continuation elements and 1 otherwise
+        pub fn width(&self) -> usize {
+            if self.is_continuation() {
+                0
+            } else {
+                unicode::grapheme_width(&self.grapheme).clamp(1, 2)
+            }
+        }
+
+        /// Replaces the grapheme with a space, keeping the colors
+        fn blank(&mut self) {
+            self.grapheme = " ".to_string();
+        }
+
+        pub fn fg_color(&self) -> Color {
+            self.fg_color
+        }
+
+        pub fn bg_color(&self) -> Color {
+            self.bg_color
+        }
+
+        pub fn attributes(&self) -> Attributes {
+            self.attributes
+        }
+
+        pub fn underline_color(&self) -> Color {
+            self.underline_color
+        }
+
+        pub fn fg(&self) -> String {
+            self.fg_color.fg()
+        }
+
+        pub fn bg(&self) -> String {
+            self.bg_color.bg()
+        }
+        // Method to reverse the foreground and background colors
+        pub fn reverse_colors(&mut self) {
+            std::mem::swap(&mut self.fg_color, &mut self.bg_color);
+        }
+    }
+
+    /// The last frame written to the screen by ```display_raw()```.
+    ///
+    /// Each frame is compared against this one so that only the cells which
+    /// changed have to be written to the terminal.
+    static FRONT: OnceLock<Mutex<Option<Frame>>> = OnceLock::new();
+
+    /// A frame of elements which has been written to the screen.
+    struct Frame {
+        width: usize,
+        height: usize,
+        elements: Vec<Element>,
+    }
+
+    /// Handle to hold terminal values.
+    ///
+    /// At its current state this is only used to return the terminal to its
+    /// original state once the application is closed. A copy of the previous
+    /// state of the terminal is kept, and reverted to when
+    /// ```Handle::drop()``` is called.
+    ///
+    /// The terminal is also restored if the application panics or is killed
+    /// by SIGINT, SIGTERM, SIGHUP or SIGQUIT, and is taken out of and back
+    /// into raw mode when the application is suspended with SIGTSTP and
+    /// continued with SIGCONT.
+    ///
+    /// Only one handle can be alive at a time, and ```init()``` fails until
+    /// it has been dropped.
+    pub struct Handle {
+        _private: (),
     }
 
-    #[cfg(unix)]
     impl Drop for Handle {
         fn drop(&mut self) {
-            // Restore terminal attributes
+            restore();
+            INITIALIZED.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Whether a ```Handle``` is alive. A second one would save the state
+    /// the first one put the terminal in as the one to restore.
+    static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+    /// Thread which called ```init()```, whose panics restore the terminal
+    static INIT_THREAD: OnceLock<Mutex<Option<ThreadId>>> = OnceLock::new();
+
+    /// Everything needed to take the terminal out of the state ```init()```
+    /// puts it in, and to put it back.
+    #[derive(Clone, Copy)]
+    struct State {
+        #[cfg(unix)]
+        original_termios: libc::termios,
+        #[cfg(unix)]
+        termios: libc::termios,
+        options: InitOptions,
+    }
+
+    /// State of the terminal while a ```Handle``` is alive. This is kept
+    /// here instead of in the handle so that the panic hook and signal
+    /// handlers are able to restore it.
+    static ACTIVE: OnceLock<Mutex<Option<State>>> = OnceLock::new();
+
+    /// Returns the state of the terminal while a ```Handle``` is alive,
+    /// taking it out if ```take``` is set.
+    ///
+    /// This never blocks, as it can be called while panicking with the lock
+    /// already held.
+    fn active(take: bool) -> Option<State> {
+        let mut active = match ACTIVE.get()?.try_lock() {
+            Ok(active) => active,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+
+        if take {
+            active.take()
+        } else {
+            *active
+        }
+    }
+
+    /// Switches the terminal to the alternate screen buffer and raw mode.
+    fn enter(state: &State) -> Result<(), &'static str> {
+        #[cfg(unix)]
+        {
+            // Apply the raw mode settings
+            let file_descriptor = std::io::stdin().as_raw_fd();
+            if unsafe { tcsetattr(file_descriptor, TCSANOW, &state.termios) } != 0 {
+                return Err("Failed to set terminal attributes");
+            }
+        }
+
+        let options = &state.options;
+        let mut buf = String::new();
+
+        if options.alternate_screen {
+            buf.push_str(SCREEN_BUFFER_ALT);
+        }
+
+        if options.hide_cursor {
+            buf.push_str(&format!("{ESC}[?25l"));
+        }
+
+        // Report mouse events in the SGR encoding, which isn't limited to
+        // 223 columns
+        if let Some(mode) = options.mouse.mode() {
+            buf.push_str(&format!("{ESC}[?{mode}h{ESC}[?1006h"));
+        }
+
+        if options.bracketed_paste {
+            buf.push_str(&format!("{ESC}[?2004h"));
+        }
+
+        if options.focus_events {
+            buf.push_str(&format!("{ESC}[?1004h"));
+        }
+
+        if options.keyboard_flags != 0 {
+            buf.push_str(&push_keyboard_flags(options.keyboard_flags));
+        }
+
+        let stdout = std::io::stdout();
+        let mut handle = stdout.lock();
+
+        write!(handle, "{buf}").map_err(|_| "Failed to write to handle")?;
+        handle.flush().map_err(|_| "Failed to flush handle")?;
+
+        // The screen was just cleared, so the previous frame is gone
+        redraw();
+
+        Ok(())
+    }
+
+    /// Returns the sequence pushing the keyboard flags onto the terminal's
+    /// stack, so that popping them restores whatever was there before.
+    fn push_keyboard_flags(flags: u8) -> String {
+        KEYBOARD_FLAGS.store(flags, Ordering::Relaxed);
+        format!("{ESC}[>{flags}u")
+    }
+
+    /// Returns the terminal to how it was before ```enter()```.
+    ///
+    /// This carries on as far as it can when something fails, since it is
+    /// also used while the application is crashing.
+    fn leave(state: &State) {
+        let options = &state.options;
+        let mut buf = String::new();
+
+        // Return to the keyboard protocol used before ```init()```
+        if options.keyboard_flags != 0 {
+            buf.push_str(&format!("{ESC}[<u"));
+            KEYBOARD_FLAGS.store(0, Ordering::Relaxed);
+        }
+
+        if options.focus_events {
+            buf.push_str(&format!("{ESC}[?1004l"));
+        }
+
+        if options.bracketed_paste {
+            buf.push_str(&format!("{ESC}[?2004l"));
+        }
+
+        if let Some(mode) = options.mouse.mode() {
+            buf.push_str(&format!("{ESC}[?1006l{ESC}[?{mode}l"));
+        }
+
+        // Reset colors and attributes, show the cursor and switch to the
+        // original screen buffer
+        buf.push_str(&format!("{ESC}[0m{ESC}[?25h"));
+        if options.alternate_screen {
+            buf.push_str(SCREEN_BUFFER_DEF);
+        }
+
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "{buf}");
+        let _ = stdout.flush();
+
+        // Restore terminal attributes
+        #[cfg(unix)]
+        {
             let file_descriptor = std::io::stdin().as_raw_fd();
+            if unsafe { tcsetattr(file_descriptor, TCSANOW, &state.original_termios) } != 0 {
+                eprintln!("Failed to restore terminal attributes")
+            }
+        }
+    }
+
+    /// Restores the terminal if a ```Handle``` is alive.
+    fn restore() {
+        if let Some(state) = active(true) {
+            leave(&state);
+        }
+    }
+
+    /// Installs the panic hook, and on Unix-like systems the signal handlers,
+    /// which restore the terminal. Does nothing if already installed.
+    fn install_hooks() -> Result<(), &'static str> {
+        static PANIC_HOOK: Once = Once::new();
+        PANIC_HOOK.call_once(|| {
+            // Leave the alternate screen first so the panic message stays
+            // visible afterwards
+            // Panics on other threads may be caught, or seen through
+            // ```JoinHandle::join()```, so those leave the terminal to the
+            // ```Handle``` being dropped
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                let init_thread = INIT_THREAD
+                    .get()
+                    .and_then(|thread| *thread.lock().unwrap_or_else(|error| error.into_inner()));
+                if init_thread == Some(thread::current().id()) {
+                    restore();
+                }
+                previous(info);
+            }));
+        });
 
-            if unsafe { tcsetattr(file_descriptor, TCSANOW, &self.original_termios) } != 0 {
-                eprintln!("Failed to restore terminal attributes")
-            }
+        #[cfg(unix)]
+        watch_signals()?;
 
-            // Switch to original screen buffer, clear screen and reset cursor
-            let mut stdout = std::io::stdout();
-            //write!(stdout, "{SCREEN_BUFFER_DEF}{ESC}[h").unwrap();
-            stdout.flush().unwrap();
-        }
+        Ok(())
     }
 
     /// Initializes the terminal for use by the engine
@@ -487,107 +2925,412 @@ pub mod terminal {
     /// to function correctly, so the ```Handle``` should not be dropped
     /// until the engine is exiting.
     ///
+    /// A panic on the thread which called this restores the terminal, so
+    /// that the panic message can be read, even if the panic is then caught
+    /// with ```std::panic::catch_unwind()```. Panics on other threads leave
+    /// the terminal as it is until the ```Handle``` is dropped.
+    ///
+    /// This uses the defaults of ```InitOptions```, which can be used instead
+    /// to pick which modes the terminal is put into.
+    ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use a5c11::core::terminal::init;
     /// let _handle = init().expect("Failed to initialize terminal");
     /// ```
     pub fn init() -> Result<Handle, &'static str> {
-        #[cfg(unix)]
-        {
-            // Create a new termios
-            let file_descriptor = std::io::stdin().as_raw_fd();
-            println!("File descriptor: {}", file_descriptor);
-            let mut termios = unsafe {
-                let mut termios = std::mem::zeroed();
-                if tcgetattr(file_descriptor, &mut termios) != 0 {
-                    return Err("Failed to get terminal attributes");
-                }
-                termios
-            };
+        InitOptions::new().init()
+    }
 
-            // Save the previous termios so that we can restore it later
-            let original_termios = termios.clone();
+    /// Initializes the terminal like ```init()```, and asks the terminal to
+    /// report keys using the kitty keyboard protocol with the given flags.
+    ///
+    /// This is the same as ```InitOptions::new().keyboard(flags).init()```.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use a5c11::core::terminal::{init_with_keyboard, KITTY_HELD_KEYS};
+    /// let _handle = init_with_keyboard(KITTY_HELD_KEYS).expect("Failed to initialize terminal");
+    /// ```
+    pub fn init_with_keyboard(flags: u8) -> Result<Handle, &'static str> {
+        InitOptions::new().keyboard(flags).init()
+    }
 
-            // Enable raw mode (disable canonical mode and echo)
+    /// Which mouse events the terminal reports.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum MouseTracking {
+        /// No mouse events, leaving the mouse to the terminal
+        #[default]
+        Off,
+        /// Buttons being pressed and released, and the wheel being scrolled
+        Buttons,
+        /// Everything in ```Buttons```, and the mouse moving while a button
+        /// is held
+        Drag,
+        /// Everything in ```Drag```, and the mouse moving with no buttons
+        /// held
+        Motion,
+    }
 
-            // Disable canonical mode and echo
-            termios.c_lflag &= !(ICANON | ECHO);
+    impl MouseTracking {
+        /// Returns the private mode which enables this kind of tracking
+        fn mode(self) -> Option<u16> {
+            match self {
+                Self::Off => None,
+                Self::Buttons => Some(1000),
+                Self::Drag => Some(1002),
+                Self::Motion => Some(1003),
+            }
+        }
+    }
 
-            // Disable flow control
-            termios.c_iflag &= !(IXON | IXOFF);
+    /// Options for which modes ```init()``` puts the terminal into.
+    ///
+    /// Everything is undone when the returned ```Handle``` is dropped. By
+    /// default the terminal is put in raw mode without signals, switched to
+    /// the alternate screen buffer, the cursor is hidden, pastes and focus
+    /// changes are reported and the terminal is asked what it supports,
+    /// while mouse reporting is left off.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use a5c11::core::terminal::{InitOptions, MouseTracking};
+    /// let _handle = InitOptions::new()
+    ///     .signals(true)
+    ///     .mouse(MouseTracking::Drag)
+    ///     .init()
+    ///     .expect("Failed to initialize terminal");
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InitOptions {
+        signals: bool,
+        alternate_screen: bool,
+        hide_cursor: bool,
+        mouse: MouseTracking,
+        bracketed_paste: bool,
+        focus_events: bool,
+        keyboard_flags: u8,
+        probe: bool,
+    }
 
-            termios.c_cc[VMIN] = 1; // Minimum number of characters for non-blocking reads
-            termios.c_cc[VTIME] = 0; // Timeout for reads
+    impl Default for InitOptions {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
 
-            // Apply the raw mode settings
-            if unsafe { tcsetattr(file_descriptor, TCSANOW, &termios) } != 0 {
-                return Err("Failed to set terminal attributes");
+    impl InitOptions {
+        pub fn new() -> Self {
+            Self {
+                signals: false,
+                alternate_screen: true,
+                hide_cursor: true,
+                mouse: MouseTracking::Off,
+                bracketed_paste: true,
+                focus_events: true,
+                keyboard_flags: 0,
+                probe: true,
             }
+        }
 
-            let stdout = std::io::stdout();
-            let mut handle = stdout.lock();
+        /// Whether Ctrl+C, Ctrl+Z and Ctrl+\\ raise signals instead of being
+        /// read as keys
+        pub fn signals(mut self, enabled: bool) -> Self {
+            self.signals = enabled;
+            self
+        }
 
-            // Switch to alternate screen buffer
-            write!(handle, "{SCREEN_BUFFER_ALT}").map_err(|_| "Failed to write to handle")?;
-            handle.flush().map_err(|_| "Failed to flush handle")?;
+        /// Whether to draw on the alternate screen buffer, which leaves the
+        /// contents of the terminal as they were afterwards
+        pub fn alternate_screen(mut self, enabled: bool) -> Self {
+            self.alternate_screen = enabled;
+            self
+        }
 
-            return Ok(Handle {
-                original_termios,
-                _termios: termios,
-            });
+        pub fn hide_cursor(mut self, hidden: bool) -> Self {
+            self.hide_cursor = hidden;
+            self
         }
 
-        #[cfg(windows)]
-        const STD_OUTPUT_HANDLE: u32 = -11i32 as u32; // Constant for standard output handle.
+        /// Which mouse events the terminal reports instead of handling them
+        /// itself (see ```keyboard::set_mouse_callback()```)
+        pub fn mouse(mut self, tracking: MouseTracking) -> Self {
+            self.mouse = tracking;
+            self
+        }
 
-        #[cfg(windows)]
-        {
-            let stdout = std::io::stdout();
-            let mut handle = stdout.lock();
+        /// Whether pasted text is marked so it can be told apart from typing,
+        /// and read as a single ```keyboard::Event::Paste```
+        pub fn bracketed_paste(mut self, enabled: bool) -> Self {
+            self.bracketed_paste = enabled;
+            self
+        }
 
-            unsafe {
-                let h_stdout: HANDLE = GetStdHandle(STD_OUTPUT_HANDLE);
-                if h_stdout == INVALID_HANDLE_VALUE {
-                    return Err("Failed to get standard output handle...");
+        /// Whether the terminal reports gaining and losing focus, read as
+        /// ```keyboard::Event::FocusGained``` and ```FocusLost```
+        pub fn focus_events(mut self, enabled: bool) -> Self {
+            self.focus_events = enabled;
+            self
+        }
+
+        /// Asks the terminal to report keys using the kitty keyboard
+        /// protocol with the given flags.
+        ///
+        /// Terminals which don't support the protocol keep reporting keys as
+        /// usual. Passing ```KITTY_HELD_KEYS``` lets the keyboard module
+        /// report keys being released, which terminals otherwise never do.
+        /// The previous protocol is restored when the ```Handle``` is
+        /// dropped. Windows consoles don't support the protocol, so the
+        /// flags are ignored there.
+        pub fn keyboard(mut self, flags: u8) -> Self {
+            self.keyboard_flags = flags;
+            self
+        }
+
+        /// Whether to ask the terminal what it supports with
+        /// ```caps::probe()```, once it is on the alternate screen.
+        ///
+        /// This usually takes a few milliseconds, but waits for up to 200
+        /// milliseconds for terminals which don't answer. Without it, the
+        /// capabilities are guessed from the environment.
+        pub fn probe(mut self, enabled: bool) -> Self {
+            self.probe = enabled;
+            self
+        }
+
+        /// Initializes the terminal with these options (see ```init()```)
+        ///
+        /// Fails if the terminal is already initialized, until its
+        /// ```Handle``` is dropped.
+        pub fn init(self) -> Result<Handle, &'static str> {
+            if INITIALIZED.swap(true, Ordering::SeqCst) {
+                return Err("Terminal is already initialized");
+            }
+            *INIT_THREAD.get_or_init(|| Mutex::new(None)).lock().unwrap() =
+                Some(thread::current().id());
+
+            let handle = self.start();
+            if handle.is_err() {
+                INITIALIZED.store(false, Ordering::SeqCst);
+            }
+            handle
+        }
+
+        /// Puts the terminal into the modes picked by these options
+        fn start(self) -> Result<Handle, &'static str> {
+            install_hooks()?;
+            let options = self;
+
+            #[cfg(unix)]
+            let mut state = {
+                // Create a new termios
+                let file_descriptor = std::io::stdin().as_raw_fd();
+                let mut termios = unsafe {
+                    let mut termios = std::mem::zeroed();
+                    if tcgetattr(file_descriptor, &mut termios) != 0 {
+                        return Err("Failed to get terminal attributes");
+                    }
+                    termios
+                };
+
+                // Save the previous termios so that we can restore it later
+                let original_termios = termios;
+
+                // Enable raw mode, like cfmakeraw() does: no line editing,
+                // echo, flow control, special characters or translation
+                // between carriage returns and newlines
+                termios.c_iflag &=
+                    !(IGNBRK | BRKINT | PARMRK | ISTRIP | INLCR | IGNCR | ICRNL | IXON | IXOFF);
+                termios.c_oflag &= !OPOST;
+                termios.c_lflag &= !(ECHO | ECHONL | ICANON | IEXTEN);
+                termios.c_cflag &= !(CSIZE | PARENB);
+                termios.c_cflag |= CS8;
+
+                if !options.signals {
+                    termios.c_lflag &= !ISIG;
                 }
 
-                // Enable virtual terminal processing for better control
-                let mut mode: u32 = 0;
-                if GetConsoleMode(h_stdout, &mut mode) == 0 {
-                    return Err("Failed to get console mode.");
+                termios.c_cc[VMIN] = 1; // Minimum number of characters for non-blocking reads
+                termios.c_cc[VTIME] = 0; // Timeout for reads
+
+                State {
+                    original_termios,
+                    termios,
+                    options,
                 }
+            };
 
-                if winapi::um::consoleapi::SetConsoleMode(
-                    h_stdout,
-                    mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING,
-                ) == 0
-                {
-                    return Err("Failed to set console mode.");
+            #[cfg(windows)]
+            const STD_OUTPUT_HANDLE: u32 = -11i32 as u32; // Constant for standard output handle.
+
+            #[cfg(windows)]
+            let mut state = {
+                unsafe {
+                    let h_stdout: HANDLE = GetStdHandle(STD_OUTPUT_HANDLE);
+                    if h_stdout == INVALID_HANDLE_VALUE {
+                        return Err("Failed to get standard output handle...");
+                    }
+
+                    // Enable virtual terminal processing for better control
+                    let mut mode: u32 = 0;
+                    if GetConsoleMode(h_stdout, &mut mode) == 0 {
+                        return Err("Failed to get console mode.");
+                    }
+
+                    if winapi::um::consoleapi::SetConsoleMode(
+                        h_stdout,
+                        mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+                    ) == 0
+                    {
+                        return Err("Failed to set console mode.");
+                    }
+                }
+
+                // Consoles don't support the kitty keyboard protocol
+                State {
+                    options: InitOptions {
+                        keyboard_flags: 0,
+                        ..options
+                    },
                 }
+            };
+
+            // The keyboard flags are only pushed once the terminal is known
+            // to support them
+            let keyboard_flags = std::mem::take(&mut state.options.keyboard_flags);
+            enter(&state)?;
+
+            // Ask the terminal what it supports on the alternate screen,
+            // while nothing else is reading its replies from standard input
+            if options.probe {
+                caps::set(caps::probe(PROBE_TIMEOUT));
             }
 
-            // Switch to alternate screen buffer
-            write!(handle, "\x1b[?1049h").map_err(|_| "Failed to write to handle")?;
-            handle.flush().map_err(|_| "Failed to flush handle")?;
+            // Terminals without the protocol would keep sending keys the
+            // usual way, which are then never released, so support is
+            // asked for even when the terminal isn't probed
+            let kitty_keyboard = if options.probe {
+                caps::get().kitty_keyboard
+            } else {
+                keyboard_flags != 0 && caps::probe_keyboard(PROBE_TIMEOUT)
+            };
+
+            if keyboard_flags != 0 && kitty_keyboard {
+                let mut stdout = std::io::stdout();
+                write!(stdout, "{}", push_keyboard_flags(keyboard_flags))
+                    .and_then(|_| stdout.flush())
+                    .map_err(|_| "Failed to write to handle")?;
+                state.options.keyboard_flags = keyboard_flags;
+            }
 
-            // Hide the cursor using ANSI escape codes
-            write!(handle, "\x1b[?25l").map_err(|_| "Failed to write to handle")?;
+            *ACTIVE
+                .get_or_init(|| Mutex::new(None))
+                .lock()
+                .map_err(|_| "Failed to lock terminal state")? = Some(state);
 
-            Ok(Handle {})
+            Ok(Handle { _private: () })
         }
     }
 
+    /// Returns the kitty keyboard protocol flags requested by
+    /// ```init_with_keyboard()```, or 0 if the protocol isn't in use.
+    ///
+    /// The flags are only requested from terminals which answer that they
+    /// support the protocol.
+    pub fn keyboard_flags() -> u8 {
+        KEYBOARD_FLAGS.load(Ordering::Relaxed)
+    }
+
+    type ResizeCallback = Box<dyn Fn(usize, usize) + Send + Sync>;
+    /// The resize callback, shared so that it can be called without holding
+    /// its lock
+    type SharedResizeCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+    /// Size of the terminal window as last returned by ```size()```.
+    static SIZE: OnceLock<Mutex<Option<(usize, usize)>>> = OnceLock::new();
+    static RESIZE_CALLBACK: OnceLock<Mutex<Option<SharedResizeCallback>>> = OnceLock::new();
+
+    /// Set by the SIGWINCH handler when the cached size is out of date.
+    #[cfg(unix)]
+    static RESIZED: AtomicBool = AtomicBool::new(false);
+
+    /// Write end of the pipe the signal handlers pass signals on to the
+    /// signal thread through, or -1 if it isn't running.
+    #[cfg(unix)]
+    static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    /// Signals handled by ```watch_signals()```
+    #[cfg(unix)]
+    const SIGNALS: [libc::c_int; 7] = [
+        libc::SIGWINCH,
+        libc::SIGINT,
+        libc::SIGTERM,
+        libc::SIGHUP,
+        libc::SIGQUIT,
+        libc::SIGTSTP,
+        libc::SIGCONT,
+    ];
+
     /// Returns the size of the terminal window.
     ///
     /// More specifically, this will return the width and height of the terminal
     /// window measured in how many characters fit inside the console.
     /// Returns ```None``` if it is unable to obtain the width and height.
     ///
+    /// On Unix-like systems the size is cached once ```init()``` has been
+    /// called, and only asked for again after the window has been resized.
+    ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use a5c11::core::terminal::size;
     /// let (width, height) = size().expect("Failed to get the size of the terminal window");
     /// ```
     pub fn size() -> Option<(usize, usize)> {
+        #[cfg(unix)]
+        {
+            let watching = SIGNAL_PIPE.load(Ordering::Relaxed) >= 0;
+            let cached = *SIZE.get_or_init(|| Mutex::new(None)).lock().unwrap();
+
+            if watching && !RESIZED.swap(false, Ordering::Relaxed) && cached.is_some() {
+                return cached;
+            }
+        }
+
+        refresh_size()
+    }
+
+    /// Asks the terminal for its size and updates the cached one, calling
+    /// the resize callback if it changed.
+    fn refresh_size() -> Option<(usize, usize)> {
+        let size = query_size()?;
+
+        let previous = SIZE
+            .get_or_init(|| Mutex::new(None))
+            .lock()
+            .unwrap()
+            .replace(size);
+
+        if previous.is_some_and(|previous| previous != size) {
+            // Whatever was on the screen has been rewrapped or cut off
+            redraw();
+
+            crate::keyboard::push(crate::keyboard::Event::Resize(size.0, size.1));
+
+            // Call it without holding the lock, so that it's able to
+            // replace itself
+            let callback = RESIZE_CALLBACK
+                .get()
+                .and_then(|callback| callback.lock().unwrap().clone());
+            if let Some(callback) = callback {
+                callback(size.0, size.1);
+            }
+        }
+
+        Some(size)
+    }
+
+    /// Asks the terminal for the size of its window.
+    fn query_size() -> Option<(usize, usize)> {
         // Use libc on Unix-like systems
         #[cfg(unix)]
         {
@@ -619,53 +3362,395 @@ pub mod terminal {
         None
     }
 
+    /// Sets a function to call with the new width and height whenever the
+    /// terminal window is resized.
+    ///
+    /// On Unix-like systems the callback is called from a separate thread as
+    /// soon as the window is resized, once ```init()``` has been called.
+    /// Elsewhere resizes are only noticed when ```size()``` is called, which
+    /// ```display()``` does every frame. The callback is free to replace or
+    /// remove itself.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use a5c11::core::terminal::set_resize_callback;
+    /// set_resize_callback(Some(Box::new(|width, height| {
+    ///     eprintln!("Resized to {width}x{height}");
+    /// })));
+    /// ```
+    pub fn set_resize_callback(callback: Option<ResizeCallback>) {
+        *RESIZE_CALLBACK
+            .get_or_init(|| Mutex::new(None))
+            .lock()
+            .unwrap() = callback.map(Arc::from);
+    }
+
+    /// Installs the signal handlers and starts the thread which responds to
+    /// them. Does nothing if already running.
+    ///
+    /// Signal handlers may only do a handful of things safely, so they just
+    /// pass the signal on through a pipe to the thread, which then does the
+    /// actual work:
+    ///
+    /// - SIGWINCH refreshes the size of the terminal
+    /// - SIGINT, SIGTERM, SIGHUP and SIGQUIT restore the terminal and then
+    ///   kill the process as they normally would
+    /// - SIGTSTP restores the terminal and suspends the process, and SIGCONT
+    ///   puts the terminal back into raw mode once it continues
+    #[cfg(unix)]
+    fn watch_signals() -> Result<(), &'static str> {
+        extern "C" fn on_signal(signal: libc::c_int) {
+            // Only async-signal-safe calls are allowed in here
+            if signal == libc::SIGWINCH {
+                RESIZED.store(true, Ordering::Relaxed);
+            }
+
+            let fd = SIGNAL_PIPE.load(Ordering::Relaxed);
+            if fd >= 0 {
+                unsafe { libc::write(fd, [signal as u8].as_ptr().cast(), 1) };
+            }
+        }
+
+        /// Sets what happens when a signal is raised
+        fn handle(signal: libc::c_int, handler: libc::sighandler_t) -> Result<(), &'static str> {
+            unsafe {
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = handler;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                    return Err("Failed to install signal handler");
+                }
+            }
+
+            Ok(())
+        }
+
+        if SIGNAL_PIPE.load(Ordering::Relaxed) >= 0 {
+            return Ok(());
+        }
+
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err("Failed to create signal pipe");
+        }
+        let [read_fd, write_fd] = fds;
+
+        // A burst of signals should never block the handler
+        unsafe {
+            let flags = libc::fcntl(write_fd, libc::F_GETFL);
+            libc::fcntl(write_fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        for (i, signal) in SIGNALS.into_iter().enumerate() {
+            if let Err(error) = handle(signal, handler) {
+                // Leave things as they were, so that the size isn't cached
+                // without anything noticing when it changes
+                for signal in &SIGNALS[..i] {
+                    let _ = handle(*signal, libc::SIG_DFL);
+                }
+                unsafe {
+                    libc::close(read_fd);
+                    libc::close(write_fd);
+                }
+                return Err(error);
+            }
+        }
+
+        // Only start passing signals on, and trusting the cached size, once
+        // every handler is installed
+        SIGNAL_PIPE.store(write_fd, Ordering::Relaxed);
+
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 64];
+            loop {
+                let count =
+                    unsafe { libc::read(read_fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+                if count < 0
+                    && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+                {
+                    continue;
+                }
+                if count <= 0 {
+                    break;
+                }
+
+                for signal in buffer[..count as usize]
+                    .iter()
+                    .map(|signal| *signal as libc::c_int)
+                {
+                    match signal {
+                        libc::SIGWINCH => {
+                            if RESIZED.swap(false, Ordering::Relaxed) {
+                                refresh_size();
+                            }
+                        }
+                        libc::SIGTSTP => {
+                            if let Some(state) = active(false) {
+                                leave(&state);
+                            }
+
+                            // Stop for real, which returns once continued
+                            let _ = handle(libc::SIGTSTP, libc::SIG_DFL);
+                            unsafe { libc::raise(libc::SIGTSTP) };
+                            let _ = handle(libc::SIGTSTP, handler);
+                        }
+                        libc::SIGCONT => {
+                            if let Some(state) = active(false) {
+                                let _ = enter(&state);
+                            }
+                        }
+                        _ => {
+                            restore();
+
+                            let _ = handle(signal, libc::SIG_DFL);
+                            unsafe { libc::raise(signal) };
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
     /// Displays an array of elements on the screen
     ///
     /// The array of elements is expected to be a flattened array arranged
     /// from left to right, then top to bottom, like a book.
     ///
+    /// Wide graphemes must be followed by a continuation element, otherwise
+    /// they are shown as a space so that the rest of the row stays in place.
+    ///
+    /// Only the elements which changed since the previous call are written,
+    /// using cursor movements to skip over the ones which stayed the same.
+    /// The whole screen is redrawn on the first call, whenever the terminal
+    /// has been resized, or after ```redraw()``` has been called.
+    ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
+    /// # use a5c11::core::terminal::display_raw;
     /// display_raw(&vec![]).unwrap();
     /// ```
-    pub fn display_raw(elements: &Vec<Element>) -> Result<(), &'static str> {
+    pub fn display_raw(elements: &[Element]) -> Result<(), &'static str> {
         let stdout = std::io::stdout();
         let mut handle = stdout.lock();
 
         // Get terminal size
         let (width, height) = size().ok_or("Failed to get display size")?;
+        let elements = elements
+            .get(..(height * width))
+            .ok_or("Index out of bounds")?;
+        let elements = &normalize(elements, width);
+
+        let mut front = FRONT
+            .get_or_init(|| Mutex::new(None))
+            .lock()
+            .map_err(|_| "Failed to lock front buffer")?;
+
+        let buf = encode_frame(front.as_ref(), elements, width, &caps::get());
+        if !buf.is_empty() {
+            let written = handle
+                .write_all(buf.as_bytes())
+                .map_err(|_| "Failed to write to handle")
+                .and_then(|_| handle.flush().map_err(|_| "Failed to flush handle"));
+
+            // The terminal may be showing part of the frame, so the next one
+            // can't be compared against either frame
+            if let Err(error) = written {
+                *front = None;
+                return Err(error);
+            }
+        }
+
+        *front = Some(Frame {
+            width,
+            height,
+            elements: elements.to_vec(),
+        });
+
+        Ok(())
+    }
+
+    /// Returns the escape sequences and text which change the screen from
+    /// the previous frame to a screen of elements, or an empty string if
+    /// nothing changed.
+    ///
+    /// Everything is redrawn when there's no previous frame, or it had a
+    /// different size.
+    fn encode_frame(
+        previous: Option<&Frame>,
+        elements: &[Element],
+        width: usize,
+        capabilities: &caps::Capabilities,
+    ) -> String {
+        let height = elements.len() / width.max(1);
+
+        // Only compare against the previous frame if it has the same size,
+        // otherwise everything has to be redrawn
+        let previous = match previous {
+            Some(frame) if frame.width == width && frame.height == height => Some(&frame.elements),
+            _ => None,
+        };
 
         // Use a buffer for efficient printing
-        // Don't entirely know why I need to add 33 specifically but if I don't the buffer will resize
         let mut buf = String::with_capacity(height * width);
 
-        // Clear screen
-        buf.push_str("\x1b[2J\x1b[H");
+        // Index of the cell the cursor is currently on, if known
+        let mut cursor = None;
+
+        if previous.is_none() {
+            // Clear screen
+            buf.push_str("\x1b[2J\x1b[H");
+            cursor = Some(0);
+        }
+
+        // Push changed elements to the buffer
+        let depth = capabilities.color_depth;
+        let mut last: Option<&Element> = None;
+        let changed = |i: usize| previous.is_none_or(|previous| previous[i] != elements[i]);
+        for (i, element) in elements.iter().enumerate() {
+            // A wide grapheme is written along with its continuation, so it
+            // has to be rewritten whenever either of them changes
+            let wide = element.width() == 2;
+            if !(changed(i) || wide && changed(i + 1)) {
+                continue;
+            }
+
+            // Continuations are covered by the wide grapheme before them,
+            // which was written above
+            if element.is_continuation() {
+                continue;
+            }
+
+            // Move the cursor if the previous cell wasn't written
+            if cursor != Some(i) {
+                buf.push_str(&format!("{ESC}[{};{}H", i / width + 1, i % width + 1));
+            }
+
+            // The attributes left over from before this frame aren't
+            // known, so start from a clean slate
+            if last.is_none() {
+                buf.push_str(&format!("{ESC}[0m"));
+            }
+
+            if last.is_none_or(|last| last.fg_color != element.fg_color) {
+                buf.push_str(&element.fg_color.sgr(false, depth));
+            }
+
+            if last.is_none_or(|last| last.bg_color != element.bg_color) {
+                buf.push_str(&element.bg_color.sgr(true, depth));
+            }
 
-        // Push elements to the buffer
-        let mut last: Option<(u8, u8)> = None;
-        for i in 0..(height * width) {
-            let element = elements.get(i).ok_or("Index out of bounds")?;
+            let attributes = last.map_or(Attributes::NONE, |last| last.attributes);
+            let mut params =
+                attributes.transition(element.attributes, capabilities.styled_underline);
 
-            if last.is_none() || last.unwrap().0 != element.fg_code {
-                buf.push_str(&element.fg());
+            let underline_color = last.map_or(Color::Default, |last| last.underline_color);
+            if capabilities.styled_underline && underline_color != element.underline_color {
+                params.push(element.underline_color.underline_param(depth));
             }
 
-            if last.is_none() || last.unwrap().1 != element.bg_code {
-                buf.push_str(&element.bg());
+            if !params.is_empty() {
+                buf.push_str(&format!("{ESC}[{}m", params.join(";")));
             }
 
-            buf.push_str(&element.char().to_string());
+            // Terminals which don't show UTF-8 would print every byte
+            if capabilities.unicode || element.grapheme().is_ascii() {
+                buf.push_str(element.grapheme());
+            } else {
+                buf.push_str(if wide { "??" } else { "?" });
+            }
 
-            last = Some((element.fg_code, element.bg_code));
+            last = Some(element);
+            cursor = Some(i + element.width());
         }
-        write!(handle, "{}", buf).map_err(|_| "Failed to write to handle")?;
 
-        // Flush handle
-        handle.flush().map_err(|_| "Failed to flush handle")?;
+        if buf.is_empty() {
+            return buf;
+        }
 
-        Ok(())
+        // Have the terminal show the frame all at once instead of as it's
+        // being written
+        if capabilities.synchronized_output {
+            buf.insert_str(0, &format!("{ESC}[?2026h"));
+            buf.push_str(&format!("{ESC}[?2026l"));
+        }
+
+        buf
+    }
+
+    /// Returns a copy of a screen of elements where every wide grapheme is
+    /// followed by its continuation, replacing wide graphemes and
+    /// continuations which lost their other half with spaces.
+    fn normalize(elements: &[Element], width: usize) -> Vec<Element> {
+        let mut elements = elements.to_vec();
+
+        for i in 0..elements.len() {
+            let column = i % width;
+            if elements[i].is_continuation() {
+                if column == 0 || elements[i - 1].width() != 2 {
+                    elements[i].blank();
+                }
+            } else if elements[i].width() == 2
+                && (column + 1 == width || !elements[i + 1].is_continuation())
+            {
+                elements[i].blank();
+            }
+        }
+
+        elements
+    }
+
+    /// Writes an element onto a screen of elements, keeping wide graphemes
+    /// intact.
+    ///
+    /// A wide grapheme also covers the cell after it. Any wide grapheme
+    /// partially overwritten by the element is replaced with a space, as
+    /// terminals would otherwise leave half of it behind.
+    fn place(elements: &mut [Element], width: usize, i: usize, element: Element) {
+        // Continuations are placed along with their wide grapheme
+        if element.is_continuation() {
+            return;
+        }
+
+        let column = i % width;
+        let wide = element.width() == 2 && column + 1 < width;
+        let covered = if wide { i..i + 2 } else { i..i + 1 };
+
+        for j in covered.clone() {
+            if elements[j].is_continuation() && j % width > 0 {
+                elements[j - 1].blank();
+            }
+            if elements[j].width() == 2 && (j + 1) % width > 0 {
+                elements[j + 1].blank();
+            }
+        }
+
+        if wide {
+            elements[i + 1] = element.continuation();
+            elements[i] = element;
+        } else {
+            elements[i] = element;
+            if elements[i].width() == 2 {
+                // There is no room for the second column at the end of a row
+                elements[i].blank();
+            }
+        }
+    }
+
+    /// Forces the next call to ```display_raw()``` to redraw the whole
+    /// screen instead of only the elements which changed.
+    ///
+    /// Useful when something other than ```display_raw()``` has written to
+    /// the terminal.
+    pub fn redraw() {
+        if let Some(front) = FRONT.get() {
+            *front.lock().unwrap() = None;
+        }
     }
 
     /// Displays a list of ```Sprite```s on the screen.
@@ -688,7 +3773,7 @@ pub mod terminal {
                 let y = y as usize;
 
                 if x < width && y < height {
-                    display[y * width + x] = element.0;
+                    place(&mut display, width, y * width + x, element.0);
                 }
             }
 
@@ -707,6 +3792,7 @@ pub mod terminal {
     ///
     /// # Examples
     /// ```
+    /// # use a5c11::core::terminal::rgb;
     /// let color = rgb(0, 1, 0); // dark green
     /// let color = rgb(5, 0, 5); // bright magenta
     /// let color = rgb(3, 3, 3); // grey
@@ -737,13 +3823,133 @@ pub mod terminal {
             color + 16
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn capabilities() -> caps::Capabilities {
+            caps::Capabilities {
+                color_depth: ColorDepth::TrueColor,
+                unicode: true,
+                synchronized_output: false,
+                styled_underline: false,
+                ..caps::Capabilities::default()
+            }
+        }
+
+        fn frame(text: &str, width: usize) -> Frame {
+            let elements = Element::text(text, Color::Default, Color::Default);
+            Frame {
+                width,
+                height: elements.len() / width,
+                elements,
+            }
+        }
+
+        /// Encodes the change from one screen of text to another
+        fn encode(previous: &str, next: &str, width: usize) -> String {
+            let next = frame(next, width);
+            encode_frame(
+                Some(&frame(previous, width)),
+                &next.elements,
+                width,
+                &capabilities(),
+            )
+        }
+
+        /// Escape codes written before the first cell of a frame
+        const RESET: &str = "\x1b[0m\x1b[39m\x1b[49m";
+
+        fn transition(from: Attributes, to: Attributes, styled_underline: bool) -> String {
+            from.transition(to, styled_underline).join(";")
+        }
+
+        #[test]
+        fn bold_and_dim() {
+            let (bold, dim) = (Attributes::BOLD, Attributes::DIM);
+
+            assert_eq!(transition(bold, dim, true), "22;2");
+            assert_eq!(transition(dim, bold | dim, true), "1");
+            assert_eq!(transition(bold | dim, dim, true), "22;2");
+            assert_eq!(transition(bold, bold | dim, true), "2");
+            assert_eq!(transition(bold | dim, Attributes::NONE, true), "22");
+            assert_eq!(transition(bold, bold, true), "");
+        }
+
+        #[test]
+        fn underline_styles() {
+            let curly = Attributes::UNDERLINE | Attributes::CURLY_UNDERLINE;
+
+            assert_eq!(transition(Attributes::NONE, curly, true), "4:3");
+            assert_eq!(transition(Attributes::NONE, curly, false), "4");
+            assert_eq!(transition(Attributes::UNDERLINE, curly, true), "4:3");
+            assert_eq!(transition(Attributes::UNDERLINE, curly, false), "");
+            assert_eq!(transition(curly, Attributes::NONE, false), "24");
+        }
+
+        #[test]
+        fn toggled_attributes() {
+            let reverse = Attributes::REVERSE | Attributes::STRIKETHROUGH;
+
+            assert_eq!(transition(Attributes::NONE, reverse, false), "7;9");
+            assert_eq!(transition(reverse, Attributes::NONE, false), "27;29");
+            assert_eq!(
+                transition(reverse, Attributes::REVERSE | Attributes::ITALIC, false),
+                "3;29"
+            );
+        }
+
+        #[test]
+        fn unchanged_frame() {
+            assert_eq!(encode("abcdef", "abcdef", 3), "");
+        }
+
+        #[test]
+        fn one_changed_cell() {
+            assert_eq!(encode("abcdef", "abcdXf", 3), format!("\x1b[2;2H{RESET}X"));
+        }
+
+        #[test]
+        fn adjacent_changed_cells() {
+            assert_eq!(encode("abcdef", "aXYdef", 3), format!("\x1b[1;2H{RESET}XY"));
+            assert_eq!(
+                encode("abcdef", "aXcdYf", 3),
+                format!("\x1b[1;2H{RESET}X\x1b[2;2HY")
+            );
+        }
+
+        #[test]
+        fn size_change_redraws() {
+            let next = frame("abcd", 2);
+            let buf = encode_frame(
+                Some(&frame("abcdef", 3)),
+                &next.elements,
+                2,
+                &capabilities(),
+            );
+            assert_eq!(buf, format!("\x1b[2J\x1b[H{RESET}abcd"));
+
+            let buf = encode_frame(None, &next.elements, 2, &capabilities());
+            assert_eq!(buf, format!("\x1b[2J\x1b[H{RESET}abcd"));
+        }
+
+        #[test]
+        fn changed_continuation_rewrites_wide_cell() {
+            let previous = frame("日ab", 4);
+            let mut next = frame("日ab", 4);
+            assert!(next.elements[1].is_continuation());
+            next.elements[1].bg_color = Color::Rgb(1, 2, 3);
+
+            let buf = encode_frame(Some(&previous), &next.elements, 4, &capabilities());
+            assert_eq!(buf, format!("\x1b[1;1H{RESET}日"));
+        }
+    }
 }
 
 pub mod keyboard {}
 
 pub mod shapes {
-    use super::terminal::{self};
-
     /// Struct to represent a square.
     pub struct Square {
         width: usize,
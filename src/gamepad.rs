@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    mem,
+    os::fd::AsRawFd,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::keyboard::{
+    self,
+    evdev::{self, Device, EV_ABS, EV_KEY},
+    Event, Key, KeyEvent,
+};
+
+/// Gamepads by id, with ```None``` left where one was unplugged
+static GAMEPADS: OnceLock<Mutex<Vec<Option<Gamepad>>>> = OnceLock::new();
+
+/// Deadzone of each axis, in the order of ```Axis::ALL```
+static DEADZONES: OnceLock<Mutex<[f32; 6]>> = OnceLock::new();
+
+/// Default deadzone of the sticks, which rarely rest at the center
+const STICK_DEADZONE: f32 = 0.15;
+/// Default deadzone of the triggers
+const TRIGGER_DEADZONE: f32 = 0.05;
+
+/// How far a trigger has to be pulled to count as pressed, for gamepads
+/// which only report it as an axis
+const TRIGGER_THRESHOLD: f32 = 0.5;
+
+/// Button a device needs to report to count as a gamepad
+const BTN_SOUTH: u16 = 0x130;
+
+/// Axes of the hat most gamepads report their D-pad as
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+/// Axes along with their codes, from ```linux/input-event-codes.h```
+const AXIS_CODES: [(Axis, u16); 8] = [
+    (Axis::LeftX, 0x00),
+    (Axis::LeftY, 0x01),
+    (Axis::RightX, 0x03),
+    (Axis::RightY, 0x04),
+    (Axis::LeftTrigger, 0x02),
+    (Axis::RightTrigger, 0x05),
+    // Racing wheels and some gamepads report their triggers as pedals
+    (Axis::LeftTrigger, 0x0a),
+    (Axis::RightTrigger, 0x09),
+];
+
+/// Represents an analog axis of a gamepad
+///
+/// Sticks go from -1.0 to 1.0, with negative values to the left and up.
+/// Triggers go from 0.0 when released to 1.0 when fully pulled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl Axis {
+    /// Every axis, in the order they are declared
+    pub const ALL: [Axis; 6] = [
+        Axis::LeftX,
+        Axis::LeftY,
+        Axis::RightX,
+        Axis::RightY,
+        Axis::LeftTrigger,
+        Axis::RightTrigger,
+    ];
+
+    /// Returns whether the axis is a trigger rather than part of a stick
+    pub fn is_trigger(self) -> bool {
+        matches!(self, Axis::LeftTrigger | Axis::RightTrigger)
+    }
+
+    /// Returns the key a trigger is reported as once it's pulled far
+    /// enough
+    fn key(self) -> Option<Key> {
+        match self {
+            Axis::LeftTrigger => Some(Key::LeftTrigger),
+            Axis::RightTrigger => Some(Key::RightTrigger),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Represents a change read from a gamepad
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    /// A button went down or up
+    Button(Key, bool),
+    /// An axis moved to a new value, before any deadzone is applied
+    Axis(Axis, f32),
+}
+
+/// Converts the ```input_event``` structs read from a gamepad's evdev
+/// device into inputs.
+///
+/// Buttons are mapped to the gamepad variants of ```Key```, and the hat
+/// most gamepads report their D-pad as is turned into D-pad buttons.
+/// Triggers which only move an axis are also pressed once pulled halfway.
+///
+/// # Example
+/// ```
+/// # use a5c11::gamepad::{Axis, Input, Parser};
+/// let mut parser = Parser::new();
+/// parser.set_range(Axis::LeftX, -100, 100);
+///
+/// // An EV_ABS event moving the left stick fully right
+/// let mut bytes = vec![0; std::mem::size_of::<libc::input_event>() - 8];
+/// bytes.extend(3u16.to_ne_bytes());
+/// bytes.extend(0u16.to_ne_bytes());
+/// bytes.extend(100i32.to_ne_bytes());
+///
+/// assert_eq!(parser.feed(&bytes), vec![Input::Axis(Axis::LeftX, 1.0)]);
+/// ```
+#[derive(Debug)]
+pub struct Parser {
+    buffer: Vec<u8>,
+    held: Vec<Key>,
+    ranges: [(i32, i32); 6],
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    /// Creates a parser for a gamepad with the ranges of an Xbox
+    /// controller, until ```set_range()``` is called
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            held: Vec::new(),
+            ranges: Axis::ALL.map(|axis| {
+                if axis.is_trigger() {
+                    (0, 1023)
+                } else {
+                    (-32768, 32767)
+                }
+            }),
+        }
+    }
+
+    /// Sets the lowest and highest raw value the gamepad reports for an
+    /// axis
+    pub fn set_range(&mut self, axis: Axis, min: i32, max: i32) {
+        self.ranges[axis.index()] = (min, max);
+    }
+
+    /// Parses bytes read from a gamepad, returning the inputs they
+    /// completed
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Input> {
+        let mut inputs = Vec::new();
+
+        for (event_type, code, value) in evdev::decode(&mut self.buffer, bytes) {
+            match (event_type, code) {
+                (EV_KEY, _) => {
+                    if let Some(key) = Key::from_evdev(code).filter(|key| is_gamepad_key(*key)) {
+                        // Buttons aren't repeated while held
+                        if value != 2 {
+                            self.button(&mut inputs, key, value != 0);
+                        }
+                    }
+                }
+                (EV_ABS, ABS_HAT0X) => {
+                    self.button(&mut inputs, Key::DPadLeft, value < 0);
+                    self.button(&mut inputs, Key::DPadRight, value > 0);
+                }
+                (EV_ABS, ABS_HAT0Y) => {
+                    self.button(&mut inputs, Key::DPadUp, value < 0);
+                    self.button(&mut inputs, Key::DPadDown, value > 0);
+                }
+                (EV_ABS, _) => {
+                    let Some(axis) = AXIS_CODES
+                        .iter()
+                        .find(|(_, other)| *other == code)
+                        .map(|(axis, _)| *axis)
+                    else {
+                        continue;
+                    };
+
+                    let value = self.normalize(axis, value);
+                    inputs.push(Input::Axis(axis, value));
+                    if let Some(key) = axis.key() {
+                        self.button(&mut inputs, key, value >= TRIGGER_THRESHOLD);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        inputs
+    }
+
+    /// Returns the buttons currently held down
+    pub fn held(&self) -> &[Key] {
+        &self.held
+    }
+
+    /// Adds an input for a button if it changed
+    fn button(&mut self, inputs: &mut Vec<Input>, key: Key, down: bool) {
+        if self.held.contains(&key) == down {
+            return;
+        }
+
+        if down {
+            self.held.push(key);
+        } else {
+            self.held.retain(|held| *held != key);
+        }
+        inputs.push(Input::Button(key, down));
+    }
+
+    /// Scales a raw axis value to the range of the axis
+    fn normalize(&self, axis: Axis, value: i32) -> f32 {
+        let (min, max) = self.ranges[axis.index()];
+        if max <= min {
+            return 0.0;
+        }
+
+        let value = (value.clamp(min, max) - min) as f32 / (max - min) as f32;
+        if axis.is_trigger() {
+            value
+        } else {
+            value * 2.0 - 1.0
+        }
+    }
+}
+
+/// Returns whether a key is one of the gamepad variants
+fn is_gamepad_key(key: Key) -> bool {
+    key as u32 & 0x4000_0000 != 0
+}
+
+/// Represents the state of a gamepad
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gamepad {
+    name: String,
+    buttons: Vec<Key>,
+    axes: [f32; 6],
+}
+
+impl Gamepad {
+    /// Returns the name the gamepad gives itself
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the buttons being pressed
+    pub fn buttons(&self) -> &[Key] {
+        &self.buttons
+    }
+
+    pub fn pressed(&self, key: Key) -> bool {
+        self.buttons.contains(&key)
+    }
+
+    /// Returns the value of an axis with its deadzone applied
+    ///
+    /// Values within the deadzone are 0.0, and the rest are scaled so the
+    /// axis still reaches its ends.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        let value = self.axes[axis.index()];
+        let deadzone = deadzone(axis);
+        if value.abs() <= deadzone {
+            return 0.0;
+        }
+
+        value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+    }
+
+    /// Returns the value of an axis without its deadzone
+    pub fn raw_axis(&self, axis: Axis) -> f32 {
+        self.axes[axis.index()]
+    }
+}
+
+fn gamepads_lock() -> &'static Mutex<Vec<Option<Gamepad>>> {
+    GAMEPADS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn deadzones() -> &'static Mutex<[f32; 6]> {
+    DEADZONES.get_or_init(|| {
+        Mutex::new(Axis::ALL.map(|axis| {
+            if axis.is_trigger() {
+                TRIGGER_DEADZONE
+            } else {
+                STICK_DEADZONE
+            }
+        }))
+    })
+}
+
+/// Returns the ids of the gamepads plugged in
+pub fn gamepads() -> Vec<usize> {
+    gamepads_lock()
+        .lock()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .filter_map(|(id, gamepad)| gamepad.as_ref().map(|_| id))
+        .collect()
+}
+
+/// Returns the state of a gamepad, or ```None``` if it was unplugged
+pub fn get(id: usize) -> Option<Gamepad> {
+    gamepads_lock().lock().unwrap().get(id)?.clone()
+}
+
+/// Returns the deadzone of an axis
+pub fn deadzone(axis: Axis) -> f32 {
+    deadzones().lock().unwrap()[axis.index()]
+}
+
+/// Sets how far an axis has to move from rest before it counts, from 0.0
+/// to just below 1.0
+pub fn set_deadzone(axis: Axis, deadzone: f32) {
+    deadzones().lock().unwrap()[axis.index()] = deadzone.clamp(0.0, 0.99);
+}
+
+/// A gamepad being read
+struct Pad {
+    id: usize,
+    file: File,
+    parser: Parser,
+}
+
+impl Device for Pad {
+    fn open(file: File) -> Option<Self> {
+        if !evdev::has_codes(&file, EV_KEY, &[BTN_SOUTH]) {
+            return None;
+        }
+
+        let mut parser = Parser::new();
+        for (axis, code) in AXIS_CODES {
+            if let Some((min, max)) = range(&file, code) {
+                parser.set_range(axis, min, max);
+            }
+        }
+
+        let gamepad = Gamepad {
+            name: name(&file).unwrap_or_default(),
+            buttons: Vec::new(),
+            axes: [0.0; 6],
+        };
+
+        // Reuse the id of a gamepad which was unplugged
+        let mut gamepads = gamepads_lock().lock().unwrap();
+        let id = match gamepads.iter().position(Option::is_none) {
+            Some(id) => {
+                gamepads[id] = Some(gamepad);
+                id
+            }
+            None => {
+                gamepads.push(Some(gamepad));
+                gamepads.len() - 1
+            }
+        };
+        drop(gamepads);
+
+        keyboard::push(Event::GamepadConnected(id));
+        Some(Self { id, file, parser })
+    }
+
+    fn file(&self) -> &File {
+        &self.file
+    }
+
+    fn read(&mut self, bytes: &[u8]) {
+        for input in self.parser.feed(bytes) {
+            let mut gamepads = gamepads_lock().lock().unwrap();
+            let Some(Some(gamepad)) = gamepads.get_mut(self.id) else {
+                return;
+            };
+
+            match input {
+                Input::Button(key, down) => {
+                    if down {
+                        gamepad.buttons.push(key);
+                    } else {
+                        gamepad.buttons.retain(|button| *button != key);
+                    }
+                    drop(gamepads);
+                    keyboard::send(KeyEvent::new(key, down));
+                }
+                Input::Axis(axis, value) => gamepad.axes[axis.index()] = value,
+            }
+        }
+    }
+
+    /// Releases the buttons held on the gamepad and frees its id
+    fn remove(self) {
+        for key in self.parser.held() {
+            keyboard::send(KeyEvent::new(*key, false));
+        }
+
+        gamepads_lock().lock().unwrap()[self.id] = None;
+        keyboard::push(Event::GamepadDisconnected(self.id));
+    }
+}
+
+/// Returns the lowest and highest value a device reports for an axis
+fn range(file: &File, code: u16) -> Option<(i32, i32)> {
+    let mut info: libc::input_absinfo = unsafe { mem::zeroed() };
+    let request = evdev::ioc_read(0x40 + code as u64, mem::size_of::<libc::input_absinfo>());
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, &mut info) } < 0 {
+        return None;
+    }
+
+    // Axes the device doesn't have are reported with an empty range
+    (info.maximum > info.minimum).then_some((info.minimum, info.maximum))
+}
+
+/// Returns the name of a device
+fn name(file: &File) -> Option<String> {
+    let mut name = [0u8; 256];
+    let request = evdev::ioc_read(0x06, name.len());
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, name.as_mut_ptr()) } < 0 {
+        return None;
+    }
+
+    let len = name
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(name.len());
+    Some(String::from_utf8_lossy(&name[..len]).into_owned())
+}
+
+/// Starts reading gamepads on a separate thread, including ones plugged in
+/// later.
+///
+/// Buttons are sent through the keyboard module like keys, and gamepads
+/// being plugged in and unplugged are sent as events. Reading
+/// ```/dev/input/event*``` needs root or membership of the ```input```
+/// group, so this fails if none of them can be read. ```Event::Error``` is
+/// sent if the devices can no longer be read later on.
+pub fn run() -> Result<(), &'static str> {
+    let mut devices: HashMap<PathBuf, Pad> = HashMap::new();
+    // Gamepads may be plugged in later, but devices which can't be read
+    // now won't be readable then either
+    if !evdev::open_devices(&mut devices) {
+        return Err("No readable input devices in /dev/input");
+    }
+
+    evdev::spawn_reader(devices);
+
+    Ok(())
+}
@@ -1,13 +1,52 @@
 use std::{
-    ptr,
-    sync::{Mutex, OnceLock},
+    collections::VecDeque,
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Condvar, Mutex, Once, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "macos")]
+use std::ptr;
+
+#[cfg(target_os = "linux")]
+use {
+    crate::core::terminal,
+    std::sync::atomic::{AtomicBool, Ordering},
 };
 
+/// Function called whenever a key goes down or up
+type Callback = Box<dyn Fn(Key, bool) + Send>;
+
+/// Function called whenever the terminal reports a mouse event
+type MouseCallback = Box<dyn Fn(MouseEvent) + Send>;
+
 static KEYS: OnceLock<Mutex<Vec<Key>>> = OnceLock::new();
-static CALLBACK: OnceLock<Mutex<Option<Box<dyn Fn(Key, bool) + Send>>>> = OnceLock::new();
+static CALLBACK: OnceLock<Mutex<Option<Callback>>> = OnceLock::new();
+static MOUSE_CALLBACK: OnceLock<Mutex<Option<MouseCallback>>> = OnceLock::new();
+
+/// Events waiting to be taken by ```poll()``` or ```try_iter()```, along
+/// with the condition ```poll()``` waits on
+static QUEUE: OnceLock<(Mutex<VecDeque<Event>>, Condvar)> = OnceLock::new();
+
+/// Channels returned by ```subscribe()```
+static SUBSCRIBERS: OnceLock<Mutex<Vec<SyncSender<Event>>>> = OnceLock::new();
+
+/// Whether keys are read by ```evdev::run()``` rather than from the terminal
+#[cfg(target_os = "linux")]
+static EVDEV: AtomicBool = AtomicBool::new(false);
+
+/// Repeats synthesized for the rate set by ```set_repeat()```, along with
+/// the condition the repeat thread waits on
+static REPEATS: OnceLock<(Mutex<Option<Repeater>>, Condvar)> = OnceLock::new();
+
+/// Most events kept in the queue, after which the oldest ones are dropped,
+/// and in a subscriber's channel
+const QUEUE_CAPACITY: usize = 1024;
 
 /// Enum representing a key on a keyboard
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(dead_code)]
 pub enum Key {
     // A-Z
@@ -69,6 +108,21 @@ pub enum Key {
     Enter = 40,
     Backspace = 42,
     CapsLock = 57,
+    Escape = 41,
+
+    PrintScreen = 70,
+    ScrollLock = 71,
+    Pause = 72,
+    NumLock = 83,
+    Menu = 101,
+
+    // Navigation keys
+    Insert = 73,
+    Home = 74,
+    PageUp = 75,
+    Delete = 76,
+    End = 77,
+    PageDown = 78,
 
     // Function keys
     F1 = 58,
@@ -102,6 +156,35 @@ pub enum Key {
     Left = 80,
     Right = 79,
 
+    // Keypad keys
+    KeypadDivide = 84,
+    KeypadMultiply = 85,
+    KeypadSubtract = 86,
+    KeypadAdd = 87,
+    KeypadEnter = 88,
+    Keypad1 = 89,
+    Keypad2 = 90,
+    Keypad3 = 91,
+    Keypad4 = 92,
+    Keypad5 = 93,
+    Keypad6 = 94,
+    Keypad7 = 95,
+    Keypad8 = 96,
+    Keypad9 = 97,
+    Keypad0 = 98,
+    KeypadDecimal = 99,
+    KeypadEqual = 103,
+
+    // Media keys, with the ones missing from the keyboard usage page
+    // numbered by their consumer page usage
+    Mute = 127,
+    VolumeUp = 128,
+    VolumeDown = 129,
+    PlayPause = 0x000C_00CD,
+    Stop = 0x000C_00B7,
+    NextTrack = 0x000C_00B5,
+    PreviousTrack = 0x000C_00B6,
+
     // Modifier keys
     LeftCtrl = 224,
     LeftShift = 225,
@@ -136,296 +219,3379 @@ pub enum Key {
     Unknown = 0,
 }
 
-#[allow(dead_code)]
 impl Key {
-    /// Converts a key code to a `Key` enum
-    /// Returns `Key::Unknown` if the key code is not recognized
+    /// Every key, in the order they are declared
+    pub const ALL: [Key; 142] = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+        Key::N1,
+        Key::N2,
+        Key::N3,
+        Key::N4,
+        Key::N5,
+        Key::N6,
+        Key::N7,
+        Key::N8,
+        Key::N9,
+        Key::N0,
+        Key::Hyphen,
+        Key::Equal,
+        Key::LeftBracket,
+        Key::RightBracket,
+        Key::Backslash,
+        Key::Semicolon,
+        Key::Apostrophe,
+        Key::Grave,
+        Key::Comma,
+        Key::Period,
+        Key::Slash,
+        Key::Space,
+        Key::Tab,
+        Key::Enter,
+        Key::Backspace,
+        Key::CapsLock,
+        Key::Escape,
+        Key::PrintScreen,
+        Key::ScrollLock,
+        Key::Pause,
+        Key::NumLock,
+        Key::Menu,
+        Key::Insert,
+        Key::Home,
+        Key::PageUp,
+        Key::Delete,
+        Key::End,
+        Key::PageDown,
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::F13,
+        Key::F14,
+        Key::F15,
+        Key::F16,
+        Key::F17,
+        Key::F18,
+        Key::F19,
+        Key::F20,
+        Key::F21,
+        Key::F22,
+        Key::F23,
+        Key::F24,
+        Key::Up,
+        Key::Down,
+        Key::Left,
+        Key::Right,
+        Key::KeypadDivide,
+        Key::KeypadMultiply,
+        Key::KeypadSubtract,
+        Key::KeypadAdd,
+        Key::KeypadEnter,
+        Key::Keypad1,
+        Key::Keypad2,
+        Key::Keypad3,
+        Key::Keypad4,
+        Key::Keypad5,
+        Key::Keypad6,
+        Key::Keypad7,
+        Key::Keypad8,
+        Key::Keypad9,
+        Key::Keypad0,
+        Key::KeypadDecimal,
+        Key::KeypadEqual,
+        Key::Mute,
+        Key::VolumeUp,
+        Key::VolumeDown,
+        Key::PlayPause,
+        Key::Stop,
+        Key::NextTrack,
+        Key::PreviousTrack,
+        Key::LeftCtrl,
+        Key::LeftShift,
+        Key::LeftAlt,
+        Key::LeftGui,
+        Key::RightCtrl,
+        Key::RightShift,
+        Key::RightAlt,
+        Key::RightGui,
+        Key::DPadUp,
+        Key::DPadDown,
+        Key::DPadLeft,
+        Key::DPadRight,
+        Key::Start,
+        Key::Back,
+        Key::LeftThumb,
+        Key::RightThumb,
+        Key::LeftShoulder,
+        Key::RightShoulder,
+        Key::North,
+        Key::East,
+        Key::South,
+        Key::West,
+        Key::LeftTrigger,
+        Key::RightTrigger,
+        Key::LeftJoystick,
+        Key::RightJoystick,
+    ];
+
+    /// Converts a usage from the HID keyboard usage page to a `Key` enum
+    /// Returns `None` if the usage is not recognized
     ///
     /// # Arguments
     ///
-    /// * `code` - The key code
+    /// * `code` - The HID usage
     ///
     /// # Returns
     ///
-    /// The `Key` enum corresponding to the key code
+    /// The `Key` enum corresponding to the usage
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::keyboard::Key;
+    /// assert_eq!(Key::from_code(0x29), Some(Key::Escape));
+    /// assert_eq!(Key::Escape.to_code(), Some(0x29));
+    /// ```
     pub fn from_code(code: u8) -> Option<Self> {
+        Self::from_hid(USAGE_PAGE_KEYBOARD, code as u32)
+    }
+
+    /// Returns the usage of the key in the HID keyboard usage page, or
+    /// ```None``` for keys which aren't on it
+    pub fn to_code(self) -> Option<u8> {
+        match self.to_hid()? {
+            (USAGE_PAGE_KEYBOARD, usage) => Some(usage as u8),
+            _ => None,
+        }
+    }
+
+    /// Converts a HID usage page and usage, as reported by IOKit, to a
+    /// `Key` enum
+    ///
+    /// Media keys are found on both the keyboard and the consumer usage
+    /// pages.
+    pub fn from_hid(page: u32, usage: u32) -> Option<Self> {
+        match (page, usage) {
+            (USAGE_PAGE_CONSUMER, 0xE2) => Some(Self::Mute),
+            (USAGE_PAGE_CONSUMER, 0xE9) => Some(Self::VolumeUp),
+            (USAGE_PAGE_CONSUMER, 0xEA) => Some(Self::VolumeDown),
+            _ => find_key(&HID_USAGES, (page, usage)),
+        }
+    }
+
+    /// Returns the HID usage page and usage of the key
+    pub fn to_hid(self) -> Option<(u32, u32)> {
+        find_code(&HID_USAGES, self)
+    }
+
+    /// Converts a Win32 virtual-key code to a `Key` enum
+    ///
+    /// The keypad Enter key shares its code with Enter, and is told apart
+    /// by the extended key flag.
+    pub fn from_virtual_key(code: u32) -> Option<Self> {
         match code {
-            4 => Some(Self::A),
-            5 => Some(Self::B),
-            6 => Some(Self::C),
-            7 => Some(Self::D),
-            8 => Some(Self::E),
-            9 => Some(Self::F),
-            10 => Some(Self::G),
-            11 => Some(Self::H),
-            12 => Some(Self::I),
-            13 => Some(Self::J),
-            14 => Some(Self::K),
-            15 => Some(Self::L),
-            16 => Some(Self::M),
-            17 => Some(Self::N),
-            18 => Some(Self::O),
-            19 => Some(Self::P),
-            20 => Some(Self::Q),
-            21 => Some(Self::R),
-            22 => Some(Self::S),
-            23 => Some(Self::T),
-            24 => Some(Self::U),
-            25 => Some(Self::V),
-            26 => Some(Self::W),
-            27 => Some(Self::X),
-            28 => Some(Self::Y),
-            29 => Some(Self::Z),
-            30 => Some(Self::N1),
-            31 => Some(Self::N2),
-            32 => Some(Self::N3),
-            33 => Some(Self::N4),
-            34 => Some(Self::N5),
-            35 => Some(Self::N6),
-            36 => Some(Self::N7),
-            37 => Some(Self::N8),
-            38 => Some(Self::N9),
-            39 => Some(Self::N0),
-
-            40 => Some(Self::Enter),
-            42 => Some(Self::Backspace),
-            43 => Some(Self::Tab),
-            44 => Some(Self::Space),
-            57 => Some(Self::CapsLock),
-
-            45 => Some(Self::Hyphen),
-            46 => Some(Self::Equal),
-            47 => Some(Self::LeftBracket),
-            48 => Some(Self::RightBracket),
-            49 => Some(Self::Backslash),
-            51 => Some(Self::Semicolon),
-            52 => Some(Self::Apostrophe),
-            53 => Some(Self::Grave),
-            54 => Some(Self::Comma),
-            55 => Some(Self::Period),
-            56 => Some(Self::Slash),
-
-            58 => Some(Self::F1),
-            59 => Some(Self::F2),
-            60 => Some(Self::F3),
-            61 => Some(Self::F4),
-            62 => Some(Self::F5),
-            63 => Some(Self::F6),
-            64 => Some(Self::F7),
-            65 => Some(Self::F8),
-            66 => Some(Self::F9),
-            67 => Some(Self::F10),
-            68 => Some(Self::F11),
-            69 => Some(Self::F12),
-            70 => Some(Self::F13),
-            71 => Some(Self::F14),
-            72 => Some(Self::F15),
-            73 => Some(Self::F16),
-            74 => Some(Self::F17),
-            75 => Some(Self::F18),
-            76 => Some(Self::F19),
-            77 => Some(Self::F20),
-            78 => Some(Self::F21),
-            79 => Some(Self::F22),
-            80 => Some(Self::F23),
-            81 => Some(Self::F24),
-
-            82 => Some(Self::Up),
-            83 => Some(Self::Down),
-            84 => Some(Self::Left),
-            85 => Some(Self::Right),
-
-            224 => Some(Self::LeftCtrl),
-            225 => Some(Self::LeftShift),
-            226 => Some(Self::LeftAlt),
-            227 => Some(Self::LeftGui),
-            228 => Some(Self::RightCtrl),
-            229 => Some(Self::RightShift),
-            230 => Some(Self::RightAlt),
-            231 => Some(Self::RightGui),
-
-            _ => Some(Self::Unknown),
-        }
-    }
-}
-
-#[cfg(unix)]
-use {
-    core_foundation::{
-        base::{kCFAllocatorDefault, CFRelease, CFTypeRef},
-        runloop::{kCFRunLoopDefaultMode, CFRunLoop},
-    },
-    io_kit_sys::{
-        hid::{
-            base::IOHIDValueRef,
-            element::{IOHIDElementGetUsage, IOHIDElementGetUsagePage},
-            keys::{kIOHIDDeviceKey, kIOHIDOptionsTypeNone},
-            manager::{
-                kIOHIDManagerOptionNone, IOHIDManagerCreate, IOHIDManagerOpen,
-                IOHIDManagerRegisterInputValueCallback, IOHIDManagerScheduleWithRunLoop,
-                IOHIDManagerSetDeviceMatching,
-            },
-            value::{IOHIDValueGetElement, IOHIDValueGetIntegerValue},
-        },
-        ret::kIOReturnSuccess,
-        IOServiceMatching,
-    },
-};
+            // Codes which don't tell left and right apart
+            0x10 => Some(Self::LeftShift),
+            0x11 => Some(Self::LeftCtrl),
+            0x12 => Some(Self::LeftAlt),
+            _ => find_key(&VIRTUAL_KEYS, code),
+        }
+    }
+
+    /// Returns the Win32 virtual-key code of the key
+    pub fn to_virtual_key(self) -> Option<u32> {
+        find_code(&VIRTUAL_KEYS, self)
+    }
+
+    /// Converts a Linux input event code, as read from
+    /// ```/dev/input/event*```, to a `Key` enum
+    pub fn from_evdev(code: u16) -> Option<Self> {
+        find_key(&EVDEV_CODES, code)
+    }
+
+    /// Returns the Linux input event code of the key
+    pub fn to_evdev(self) -> Option<u16> {
+        find_code(&EVDEV_CODES, self)
+    }
+
+    /// Returns the bytes a terminal sends for the key when pressed with no
+    /// modifiers held
+    ///
+    /// Keys without a traditional sequence use the one from the kitty
+    /// keyboard protocol, so only terminals supporting it send them.
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::keyboard::{Event, Key, Parser};
+    /// let mut parser = Parser::new();
+    /// let sequence = Key::Up.to_sequence().unwrap();
+    /// assert_eq!(sequence, "\x1b[A");
+    ///
+    /// let events = parser.feed(sequence.as_bytes());
+    /// assert!(matches!(&events[0], Event::Key(event) if event.key == Key::Up));
+    /// ```
+    pub fn to_sequence(self) -> Option<&'static str> {
+        find_code(&TERMINAL_SEQUENCES, self)
+    }
+}
+
+/// Writes the name of the key, as used by ```action::ActionMap``` files
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl std::str::FromStr for Key {
+    type Err = &'static str;
+
+    /// Reads a key from its name
+    ///
+    /// # Example
+    /// ```
+    /// # use a5c11::keyboard::Key;
+    /// assert_eq!("Escape".parse(), Ok(Key::Escape));
+    /// assert_eq!(Key::Escape.to_string(), "Escape");
+    /// ```
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Key::ALL
+            .into_iter()
+            .find(|key| key.to_string() == name)
+            .ok_or("Unknown key name")
+    }
+}
 
-#[cfg(unix)]
+/// HID usage page of keyboard keys
 const USAGE_PAGE_KEYBOARD: u32 = 0x07;
+/// HID usage page of media keys
+const USAGE_PAGE_CONSUMER: u32 = 0x0C;
 
-/// Input callback for input thread
-#[cfg(unix)]
-extern "C" fn input_value_callback(
-    _context: *mut std::ffi::c_void,
-    _result: i32,
-    _sender: *mut std::ffi::c_void,
-    value: IOHIDValueRef,
-) {
-    unsafe {
-        // Get the element associated with the value
-        let element = IOHIDValueGetElement(value);
+/// Returns the key found next to a code in a translation table
+fn find_key<T: PartialEq>(table: &[(Key, T)], code: T) -> Option<Key> {
+    table
+        .iter()
+        .find(|(_, other)| *other == code)
+        .map(|(key, _)| *key)
+}
 
-        // Get the usage page and usage
-        let usage_page = IOHIDElementGetUsagePage(element);
-        let usage = IOHIDElementGetUsage(element);
+/// Returns the code found next to a key in a translation table
+fn find_code<T: Copy>(table: &[(Key, T)], key: Key) -> Option<T> {
+    table
+        .iter()
+        .find(|(other, _)| *other == key)
+        .map(|(_, code)| *code)
+}
 
-        // Get the value (pressed or released)
-        let pressed = IOHIDValueGetIntegerValue(value);
+/// Shift modifier bit, as encoded by xterm-style escape sequences
+const SHIFT: u8 = 0b0001;
+/// Alt modifier bit, as encoded by xterm-style escape sequences
+const ALT: u8 = 0b0010;
+/// Ctrl modifier bit, as encoded by xterm-style escape sequences
+const CTRL: u8 = 0b0100;
+/// Super modifier bit, as encoded by xterm-style escape sequences
+const SUPER: u8 = 0b1000;
 
-        if usage_page == USAGE_PAGE_KEYBOARD {
-            let key = Key::from_code(usage as u8);
-
-            match key {
-                Some(key) => match key {
-                    Key::Unknown => {}
-                    key => {
-                        if pressed == 0 {
-                            KEYS.get().unwrap().lock().unwrap().retain(|k| k != &key);
-
-                            match CALLBACK.get().unwrap().lock().unwrap().as_ref() {
-                                Some(func) => func(key, false),
-                                _ => {}
-                            }
-                        } else {
-                            KEYS.get().unwrap().lock().unwrap().push(key.clone());
-
-                            match CALLBACK.get().unwrap().lock().unwrap().as_ref() {
-                                Some(func) => func(key, true),
-                                _ => {}
-                            }
-                        }
-                    }
-                },
-                _ => {}
+/// Set of modifier keys held during an event.
+///
+/// Left and right modifier keys aren't told apart. Modifiers are combined
+/// with ```|```.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::Modifiers;
+/// let modifiers = Modifiers::CTRL | Modifiers::SHIFT;
+/// assert!(modifiers.contains(Modifiers::CTRL));
+/// assert!(!modifiers.contains(Modifiers::ALT));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Self = Self(0);
+    pub const SHIFT: Self = Self(SHIFT);
+    pub const ALT: Self = Self(ALT);
+    pub const CTRL: Self = Self(CTRL);
+    pub const SUPER: Self = Self(SUPER);
+
+    /// Creates a set of modifiers from the bitmask used by xterm-style
+    /// escape sequences, ignoring unknown bits
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits & (SHIFT | ALT | CTRL | SUPER))
+    }
+
+    /// Returns the bitmask used by xterm-style escape sequences
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns whether every modifier in ```other``` is held
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl std::ops::BitAnd for Modifiers {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+/// How long to wait for the rest of an escape sequence before treating the
+/// escape as its own key press
+#[cfg(target_os = "linux")]
+const ESCAPE_TIMEOUT_MS: i32 = 25;
+
+/// Marker a terminal sends before pasted text in bracketed paste mode
+const PASTE_START: &[u8] = b"\x1b[200~";
+/// Marker a terminal sends after pasted text in bracketed paste mode
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// How long to wait for the end of a paste before sending the text which
+/// arrived as the whole paste
+#[cfg(target_os = "linux")]
+const PASTE_TIMEOUT_MS: i32 = 1000;
+
+/// Longest paste the parser will hold on to, after which the text which
+/// arrived is sent as the whole paste
+const MAX_PASTE_LEN: usize = 1 << 20;
+
+/// Longest escape sequence the parser will wait on before discarding it
+const MAX_SEQUENCE_LEN: usize = 32;
+
+/// Modifier bits along with the key reported for them
+const MODIFIERS: [(u8, Key); 4] = [
+    (CTRL, Key::LeftCtrl),
+    (SHIFT, Key::LeftShift),
+    (ALT, Key::LeftAlt),
+    (SUPER, Key::LeftGui),
+];
+
+/// Function keys in order, for sequences which number them
+const FUNCTION_KEYS: [Key; 24] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::F13,
+    Key::F14,
+    Key::F15,
+    Key::F16,
+    Key::F17,
+    Key::F18,
+    Key::F19,
+    Key::F20,
+    Key::F21,
+    Key::F22,
+    Key::F23,
+    Key::F24,
+];
+
+/// Keypad keys from 1 to 9, for sequences which number them
+const KEYPAD_DIGITS: [Key; 9] = [
+    Key::Keypad1,
+    Key::Keypad2,
+    Key::Keypad3,
+    Key::Keypad4,
+    Key::Keypad5,
+    Key::Keypad6,
+    Key::Keypad7,
+    Key::Keypad8,
+    Key::Keypad9,
+];
+
+/// Keys along with their HID usage page and usage
+const HID_USAGES: [(Key, (u32, u32)); 124] = [
+    (Key::A, (USAGE_PAGE_KEYBOARD, 0x04)),
+    (Key::B, (USAGE_PAGE_KEYBOARD, 0x05)),
+    (Key::C, (USAGE_PAGE_KEYBOARD, 0x06)),
+    (Key::D, (USAGE_PAGE_KEYBOARD, 0x07)),
+    (Key::E, (USAGE_PAGE_KEYBOARD, 0x08)),
+    (Key::F, (USAGE_PAGE_KEYBOARD, 0x09)),
+    (Key::G, (USAGE_PAGE_KEYBOARD, 0x0a)),
+    (Key::H, (USAGE_PAGE_KEYBOARD, 0x0b)),
+    (Key::I, (USAGE_PAGE_KEYBOARD, 0x0c)),
+    (Key::J, (USAGE_PAGE_KEYBOARD, 0x0d)),
+    (Key::K, (USAGE_PAGE_KEYBOARD, 0x0e)),
+    (Key::L, (USAGE_PAGE_KEYBOARD, 0x0f)),
+    (Key::M, (USAGE_PAGE_KEYBOARD, 0x10)),
+    (Key::N, (USAGE_PAGE_KEYBOARD, 0x11)),
+    (Key::O, (USAGE_PAGE_KEYBOARD, 0x12)),
+    (Key::P, (USAGE_PAGE_KEYBOARD, 0x13)),
+    (Key::Q, (USAGE_PAGE_KEYBOARD, 0x14)),
+    (Key::R, (USAGE_PAGE_KEYBOARD, 0x15)),
+    (Key::S, (USAGE_PAGE_KEYBOARD, 0x16)),
+    (Key::T, (USAGE_PAGE_KEYBOARD, 0x17)),
+    (Key::U, (USAGE_PAGE_KEYBOARD, 0x18)),
+    (Key::V, (USAGE_PAGE_KEYBOARD, 0x19)),
+    (Key::W, (USAGE_PAGE_KEYBOARD, 0x1a)),
+    (Key::X, (USAGE_PAGE_KEYBOARD, 0x1b)),
+    (Key::Y, (USAGE_PAGE_KEYBOARD, 0x1c)),
+    (Key::Z, (USAGE_PAGE_KEYBOARD, 0x1d)),
+    (Key::N1, (USAGE_PAGE_KEYBOARD, 0x1e)),
+    (Key::N2, (USAGE_PAGE_KEYBOARD, 0x1f)),
+    (Key::N3, (USAGE_PAGE_KEYBOARD, 0x20)),
+    (Key::N4, (USAGE_PAGE_KEYBOARD, 0x21)),
+    (Key::N5, (USAGE_PAGE_KEYBOARD, 0x22)),
+    (Key::N6, (USAGE_PAGE_KEYBOARD, 0x23)),
+    (Key::N7, (USAGE_PAGE_KEYBOARD, 0x24)),
+    (Key::N8, (USAGE_PAGE_KEYBOARD, 0x25)),
+    (Key::N9, (USAGE_PAGE_KEYBOARD, 0x26)),
+    (Key::N0, (USAGE_PAGE_KEYBOARD, 0x27)),
+    (Key::Hyphen, (USAGE_PAGE_KEYBOARD, 0x2d)),
+    (Key::Equal, (USAGE_PAGE_KEYBOARD, 0x2e)),
+    (Key::LeftBracket, (USAGE_PAGE_KEYBOARD, 0x2f)),
+    (Key::RightBracket, (USAGE_PAGE_KEYBOARD, 0x30)),
+    (Key::Backslash, (USAGE_PAGE_KEYBOARD, 0x31)),
+    (Key::Semicolon, (USAGE_PAGE_KEYBOARD, 0x33)),
+    (Key::Apostrophe, (USAGE_PAGE_KEYBOARD, 0x34)),
+    (Key::Grave, (USAGE_PAGE_KEYBOARD, 0x35)),
+    (Key::Comma, (USAGE_PAGE_KEYBOARD, 0x36)),
+    (Key::Period, (USAGE_PAGE_KEYBOARD, 0x37)),
+    (Key::Slash, (USAGE_PAGE_KEYBOARD, 0x38)),
+    (Key::Space, (USAGE_PAGE_KEYBOARD, 0x2c)),
+    (Key::Tab, (USAGE_PAGE_KEYBOARD, 0x2b)),
+    (Key::Enter, (USAGE_PAGE_KEYBOARD, 0x28)),
+    (Key::Backspace, (USAGE_PAGE_KEYBOARD, 0x2a)),
+    (Key::CapsLock, (USAGE_PAGE_KEYBOARD, 0x39)),
+    (Key::Escape, (USAGE_PAGE_KEYBOARD, 0x29)),
+    (Key::PrintScreen, (USAGE_PAGE_KEYBOARD, 0x46)),
+    (Key::ScrollLock, (USAGE_PAGE_KEYBOARD, 0x47)),
+    (Key::Pause, (USAGE_PAGE_KEYBOARD, 0x48)),
+    (Key::NumLock, (USAGE_PAGE_KEYBOARD, 0x53)),
+    (Key::Menu, (USAGE_PAGE_KEYBOARD, 0x65)),
+    (Key::Insert, (USAGE_PAGE_KEYBOARD, 0x49)),
+    (Key::Home, (USAGE_PAGE_KEYBOARD, 0x4a)),
+    (Key::PageUp, (USAGE_PAGE_KEYBOARD, 0x4b)),
+    (Key::Delete, (USAGE_PAGE_KEYBOARD, 0x4c)),
+    (Key::End, (USAGE_PAGE_KEYBOARD, 0x4d)),
+    (Key::PageDown, (USAGE_PAGE_KEYBOARD, 0x4e)),
+    (Key::F1, (USAGE_PAGE_KEYBOARD, 0x3a)),
+    (Key::F2, (USAGE_PAGE_KEYBOARD, 0x3b)),
+    (Key::F3, (USAGE_PAGE_KEYBOARD, 0x3c)),
+    (Key::F4, (USAGE_PAGE_KEYBOARD, 0x3d)),
+    (Key::F5, (USAGE_PAGE_KEYBOARD, 0x3e)),
+    (Key::F6, (USAGE_PAGE_KEYBOARD, 0x3f)),
+    (Key::F7, (USAGE_PAGE_KEYBOARD, 0x40)),
+    (Key::F8, (USAGE_PAGE_KEYBOARD, 0x41)),
+    (Key::F9, (USAGE_PAGE_KEYBOARD, 0x42)),
+    (Key::F10, (USAGE_PAGE_KEYBOARD, 0x43)),
+    (Key::F11, (USAGE_PAGE_KEYBOARD, 0x44)),
+    (Key::F12, (USAGE_PAGE_KEYBOARD, 0x45)),
+    (Key::F13, (USAGE_PAGE_KEYBOARD, 0x68)),
+    (Key::F14, (USAGE_PAGE_KEYBOARD, 0x69)),
+    (Key::F15, (USAGE_PAGE_KEYBOARD, 0x6a)),
+    (Key::F16, (USAGE_PAGE_KEYBOARD, 0x6b)),
+    (Key::F17, (USAGE_PAGE_KEYBOARD, 0x6c)),
+    (Key::F18, (USAGE_PAGE_KEYBOARD, 0x6d)),
+    (Key::F19, (USAGE_PAGE_KEYBOARD, 0x6e)),
+    (Key::F20, (USAGE_PAGE_KEYBOARD, 0x6f)),
+    (Key::F21, (USAGE_PAGE_KEYBOARD, 0x70)),
+    (Key::F22, (USAGE_PAGE_KEYBOARD, 0x71)),
+    (Key::F23, (USAGE_PAGE_KEYBOARD, 0x72)),
+    (Key::F24, (USAGE_PAGE_KEYBOARD, 0x73)),
+    (Key::Up, (USAGE_PAGE_KEYBOARD, 0x52)),
+    (Key::Down, (USAGE_PAGE_KEYBOARD, 0x51)),
+    (Key::Left, (USAGE_PAGE_KEYBOARD, 0x50)),
+    (Key::Right, (USAGE_PAGE_KEYBOARD, 0x4f)),
+    (Key::KeypadDivide, (USAGE_PAGE_KEYBOARD, 0x54)),
+    (Key::KeypadMultiply, (USAGE_PAGE_KEYBOARD, 0x55)),
+    (Key::KeypadSubtract, (USAGE_PAGE_KEYBOARD, 0x56)),
+    (Key::KeypadAdd, (USAGE_PAGE_KEYBOARD, 0x57)),
+    (Key::KeypadEnter, (USAGE_PAGE_KEYBOARD, 0x58)),
+    (Key::Keypad1, (USAGE_PAGE_KEYBOARD, 0x59)),
+    (Key::Keypad2, (USAGE_PAGE_KEYBOARD, 0x5a)),
+    (Key::Keypad3, (USAGE_PAGE_KEYBOARD, 0x5b)),
+    (Key::Keypad4, (USAGE_PAGE_KEYBOARD, 0x5c)),
+    (Key::Keypad5, (USAGE_PAGE_KEYBOARD, 0x5d)),
+    (Key::Keypad6, (USAGE_PAGE_KEYBOARD, 0x5e)),
+    (Key::Keypad7, (USAGE_PAGE_KEYBOARD, 0x5f)),
+    (Key::Keypad8, (USAGE_PAGE_KEYBOARD, 0x60)),
+    (Key::Keypad9, (USAGE_PAGE_KEYBOARD, 0x61)),
+    (Key::Keypad0, (USAGE_PAGE_KEYBOARD, 0x62)),
+    (Key::KeypadDecimal, (USAGE_PAGE_KEYBOARD, 0x63)),
+    (Key::KeypadEqual, (USAGE_PAGE_KEYBOARD, 0x67)),
+    (Key::Mute, (USAGE_PAGE_KEYBOARD, 0x7f)),
+    (Key::VolumeUp, (USAGE_PAGE_KEYBOARD, 0x80)),
+    (Key::VolumeDown, (USAGE_PAGE_KEYBOARD, 0x81)),
+    (Key::PlayPause, (USAGE_PAGE_CONSUMER, 0xcd)),
+    (Key::Stop, (USAGE_PAGE_CONSUMER, 0xb7)),
+    (Key::NextTrack, (USAGE_PAGE_CONSUMER, 0xb5)),
+    (Key::PreviousTrack, (USAGE_PAGE_CONSUMER, 0xb6)),
+    (Key::LeftCtrl, (USAGE_PAGE_KEYBOARD, 0xe0)),
+    (Key::LeftShift, (USAGE_PAGE_KEYBOARD, 0xe1)),
+    (Key::LeftAlt, (USAGE_PAGE_KEYBOARD, 0xe2)),
+    (Key::LeftGui, (USAGE_PAGE_KEYBOARD, 0xe3)),
+    (Key::RightCtrl, (USAGE_PAGE_KEYBOARD, 0xe4)),
+    (Key::RightShift, (USAGE_PAGE_KEYBOARD, 0xe5)),
+    (Key::RightAlt, (USAGE_PAGE_KEYBOARD, 0xe6)),
+    (Key::RightGui, (USAGE_PAGE_KEYBOARD, 0xe7)),
+];
+
+/// Keys along with their Win32 virtual-key codes
+const VIRTUAL_KEYS: [(Key, u32); 122] = [
+    (Key::A, 0x41),
+    (Key::B, 0x42),
+    (Key::C, 0x43),
+    (Key::D, 0x44),
+    (Key::E, 0x45),
+    (Key::F, 0x46),
+    (Key::G, 0x47),
+    (Key::H, 0x48),
+    (Key::I, 0x49),
+    (Key::J, 0x4A),
+    (Key::K, 0x4B),
+    (Key::L, 0x4C),
+    (Key::M, 0x4D),
+    (Key::N, 0x4E),
+    (Key::O, 0x4F),
+    (Key::P, 0x50),
+    (Key::Q, 0x51),
+    (Key::R, 0x52),
+    (Key::S, 0x53),
+    (Key::T, 0x54),
+    (Key::U, 0x55),
+    (Key::V, 0x56),
+    (Key::W, 0x57),
+    (Key::X, 0x58),
+    (Key::Y, 0x59),
+    (Key::Z, 0x5A),
+    (Key::N1, 0x31),
+    (Key::N2, 0x32),
+    (Key::N3, 0x33),
+    (Key::N4, 0x34),
+    (Key::N5, 0x35),
+    (Key::N6, 0x36),
+    (Key::N7, 0x37),
+    (Key::N8, 0x38),
+    (Key::N9, 0x39),
+    (Key::N0, 0x30),
+    (Key::Hyphen, 0xBD),
+    (Key::Equal, 0xBB),
+    (Key::LeftBracket, 0xDB),
+    (Key::RightBracket, 0xDD),
+    (Key::Backslash, 0xDC),
+    (Key::Semicolon, 0xBA),
+    (Key::Apostrophe, 0xDE),
+    (Key::Grave, 0xC0),
+    (Key::Comma, 0xBC),
+    (Key::Period, 0xBE),
+    (Key::Slash, 0xBF),
+    (Key::Space, 0x20),
+    (Key::Tab, 0x09),
+    (Key::Enter, 0x0D),
+    (Key::Backspace, 0x08),
+    (Key::CapsLock, 0x14),
+    (Key::Escape, 0x1B),
+    (Key::PrintScreen, 0x2C),
+    (Key::ScrollLock, 0x91),
+    (Key::Pause, 0x13),
+    (Key::NumLock, 0x90),
+    (Key::Menu, 0x5D),
+    (Key::Insert, 0x2D),
+    (Key::Home, 0x24),
+    (Key::PageUp, 0x21),
+    (Key::Delete, 0x2E),
+    (Key::End, 0x23),
+    (Key::PageDown, 0x22),
+    (Key::F1, 0x70),
+    (Key::F2, 0x71),
+    (Key::F3, 0x72),
+    (Key::F4, 0x73),
+    (Key::F5, 0x74),
+    (Key::F6, 0x75),
+    (Key::F7, 0x76),
+    (Key::F8, 0x77),
+    (Key::F9, 0x78),
+    (Key::F10, 0x79),
+    (Key::F11, 0x7A),
+    (Key::F12, 0x7B),
+    (Key::F13, 0x7C),
+    (Key::F14, 0x7D),
+    (Key::F15, 0x7E),
+    (Key::F16, 0x7F),
+    (Key::F17, 0x80),
+    (Key::F18, 0x81),
+    (Key::F19, 0x82),
+    (Key::F20, 0x83),
+    (Key::F21, 0x84),
+    (Key::F22, 0x85),
+    (Key::F23, 0x86),
+    (Key::F24, 0x87),
+    (Key::Up, 0x26),
+    (Key::Down, 0x28),
+    (Key::Left, 0x25),
+    (Key::Right, 0x27),
+    (Key::KeypadDivide, 0x6F),
+    (Key::KeypadMultiply, 0x6A),
+    (Key::KeypadSubtract, 0x6D),
+    (Key::KeypadAdd, 0x6B),
+    (Key::Keypad1, 0x61),
+    (Key::Keypad2, 0x62),
+    (Key::Keypad3, 0x63),
+    (Key::Keypad4, 0x64),
+    (Key::Keypad5, 0x65),
+    (Key::Keypad6, 0x66),
+    (Key::Keypad7, 0x67),
+    (Key::Keypad8, 0x68),
+    (Key::Keypad9, 0x69),
+    (Key::Keypad0, 0x60),
+    (Key::KeypadDecimal, 0x6E),
+    (Key::Mute, 0xAD),
+    (Key::VolumeUp, 0xAF),
+    (Key::VolumeDown, 0xAE),
+    (Key::PlayPause, 0xB3),
+    (Key::Stop, 0xB2),
+    (Key::NextTrack, 0xB0),
+    (Key::PreviousTrack, 0xB1),
+    (Key::LeftCtrl, 0xA2),
+    (Key::LeftShift, 0xA0),
+    (Key::LeftAlt, 0xA4),
+    (Key::LeftGui, 0x5B),
+    (Key::RightCtrl, 0xA3),
+    (Key::RightShift, 0xA1),
+    (Key::RightAlt, 0xA5),
+    (Key::RightGui, 0x5C),
+];
+
+/// Keys along with their Linux input event codes, from
+/// ```linux/input-event-codes.h```
+const EVDEV_CODES: [(Key, u16); 140] = [
+    (Key::A, 30),
+    (Key::B, 48),
+    (Key::C, 46),
+    (Key::D, 32),
+    (Key::E, 18),
+    (Key::F, 33),
+    (Key::G, 34),
+    (Key::H, 35),
+    (Key::I, 23),
+    (Key::J, 36),
+    (Key::K, 37),
+    (Key::L, 38),
+    (Key::M, 50),
+    (Key::N, 49),
+    (Key::O, 24),
+    (Key::P, 25),
+    (Key::Q, 16),
+    (Key::R, 19),
+    (Key::S, 31),
+    (Key::T, 20),
+    (Key::U, 22),
+    (Key::V, 47),
+    (Key::W, 17),
+    (Key::X, 45),
+    (Key::Y, 21),
+    (Key::Z, 44),
+    (Key::N1, 2),
+    (Key::N2, 3),
+    (Key::N3, 4),
+    (Key::N4, 5),
+    (Key::N5, 6),
+    (Key::N6, 7),
+    (Key::N7, 8),
+    (Key::N8, 9),
+    (Key::N9, 10),
+    (Key::N0, 11),
+    (Key::Hyphen, 12),
+    (Key::Equal, 13),
+    (Key::LeftBracket, 26),
+    (Key::RightBracket, 27),
+    (Key::Backslash, 43),
+    (Key::Semicolon, 39),
+    (Key::Apostrophe, 40),
+    (Key::Grave, 41),
+    (Key::Comma, 51),
+    (Key::Period, 52),
+    (Key::Slash, 53),
+    (Key::Space, 57),
+    (Key::Tab, 15),
+    (Key::Enter, 28),
+    (Key::Backspace, 14),
+    (Key::CapsLock, 58),
+    (Key::Escape, 1),
+    (Key::PrintScreen, 99),
+    (Key::ScrollLock, 70),
+    (Key::Pause, 119),
+    (Key::NumLock, 69),
+    (Key::Menu, 127),
+    (Key::Insert, 110),
+    (Key::Home, 102),
+    (Key::PageUp, 104),
+    (Key::Delete, 111),
+    (Key::End, 107),
+    (Key::PageDown, 109),
+    (Key::F1, 59),
+    (Key::F2, 60),
+    (Key::F3, 61),
+    (Key::F4, 62),
+    (Key::F5, 63),
+    (Key::F6, 64),
+    (Key::F7, 65),
+    (Key::F8, 66),
+    (Key::F9, 67),
+    (Key::F10, 68),
+    (Key::F11, 87),
+    (Key::F12, 88),
+    (Key::F13, 183),
+    (Key::F14, 184),
+    (Key::F15, 185),
+    (Key::F16, 186),
+    (Key::F17, 187),
+    (Key::F18, 188),
+    (Key::F19, 189),
+    (Key::F20, 190),
+    (Key::F21, 191),
+    (Key::F22, 192),
+    (Key::F23, 193),
+    (Key::F24, 194),
+    (Key::Up, 103),
+    (Key::Down, 108),
+    (Key::Left, 105),
+    (Key::Right, 106),
+    (Key::KeypadDivide, 98),
+    (Key::KeypadMultiply, 55),
+    (Key::KeypadSubtract, 74),
+    (Key::KeypadAdd, 78),
+    (Key::KeypadEnter, 96),
+    (Key::Keypad1, 79),
+    (Key::Keypad2, 80),
+    (Key::Keypad3, 81),
+    (Key::Keypad4, 75),
+    (Key::Keypad5, 76),
+    (Key::Keypad6, 77),
+    (Key::Keypad7, 71),
+    (Key::Keypad8, 72),
+    (Key::Keypad9, 73),
+    (Key::Keypad0, 82),
+    (Key::KeypadDecimal, 83),
+    (Key::KeypadEqual, 117),
+    (Key::Mute, 113),
+    (Key::VolumeUp, 115),
+    (Key::VolumeDown, 114),
+    (Key::PlayPause, 164),
+    (Key::Stop, 166),
+    (Key::NextTrack, 163),
+    (Key::PreviousTrack, 165),
+    (Key::LeftCtrl, 29),
+    (Key::LeftShift, 42),
+    (Key::LeftAlt, 56),
+    (Key::LeftGui, 125),
+    (Key::RightCtrl, 97),
+    (Key::RightShift, 54),
+    (Key::RightAlt, 100),
+    (Key::RightGui, 126),
+    (Key::DPadUp, 0x220),
+    (Key::DPadDown, 0x221),
+    (Key::DPadLeft, 0x222),
+    (Key::DPadRight, 0x223),
+    (Key::Start, 0x13b),
+    (Key::Back, 0x13a),
+    (Key::LeftThumb, 0x13d),
+    (Key::RightThumb, 0x13e),
+    (Key::LeftShoulder, 0x136),
+    (Key::RightShoulder, 0x137),
+    (Key::North, 0x133),
+    (Key::East, 0x131),
+    (Key::South, 0x130),
+    (Key::West, 0x134),
+    (Key::LeftTrigger, 0x138),
+    (Key::RightTrigger, 0x139),
+];
+
+/// Keys along with the bytes a terminal sends for them
+const TERMINAL_SEQUENCES: [(Key, &str); 124] = [
+    (Key::A, "a"),
+    (Key::B, "b"),
+    (Key::C, "c"),
+    (Key::D, "d"),
+    (Key::E, "e"),
+    (Key::F, "f"),
+    (Key::G, "g"),
+    (Key::H, "h"),
+    (Key::I, "i"),
+    (Key::J, "j"),
+    (Key::K, "k"),
+    (Key::L, "l"),
+    (Key::M, "m"),
+    (Key::N, "n"),
+    (Key::O, "o"),
+    (Key::P, "p"),
+    (Key::Q, "q"),
+    (Key::R, "r"),
+    (Key::S, "s"),
+    (Key::T, "t"),
+    (Key::U, "u"),
+    (Key::V, "v"),
+    (Key::W, "w"),
+    (Key::X, "x"),
+    (Key::Y, "y"),
+    (Key::Z, "z"),
+    (Key::N1, "1"),
+    (Key::N2, "2"),
+    (Key::N3, "3"),
+    (Key::N4, "4"),
+    (Key::N5, "5"),
+    (Key::N6, "6"),
+    (Key::N7, "7"),
+    (Key::N8, "8"),
+    (Key::N9, "9"),
+    (Key::N0, "0"),
+    (Key::Hyphen, "-"),
+    (Key::Equal, "="),
+    (Key::LeftBracket, "["),
+    (Key::RightBracket, "]"),
+    (Key::Backslash, "\\"),
+    (Key::Semicolon, ";"),
+    (Key::Apostrophe, "'"),
+    (Key::Grave, "`"),
+    (Key::Comma, ","),
+    (Key::Period, "."),
+    (Key::Slash, "/"),
+    (Key::Space, " "),
+    (Key::Tab, "\t"),
+    (Key::Enter, "\r"),
+    (Key::Backspace, "\x7f"),
+    (Key::CapsLock, "\x1b[57358u"),
+    (Key::Escape, "\x1b"),
+    (Key::PrintScreen, "\x1b[57361u"),
+    (Key::ScrollLock, "\x1b[57359u"),
+    (Key::Pause, "\x1b[57362u"),
+    (Key::NumLock, "\x1b[57360u"),
+    (Key::Menu, "\x1b[57363u"),
+    (Key::Insert, "\x1b[2~"),
+    (Key::Home, "\x1b[H"),
+    (Key::PageUp, "\x1b[5~"),
+    (Key::Delete, "\x1b[3~"),
+    (Key::End, "\x1b[F"),
+    (Key::PageDown, "\x1b[6~"),
+    (Key::F1, "\x1bOP"),
+    (Key::F2, "\x1bOQ"),
+    (Key::F3, "\x1bOR"),
+    (Key::F4, "\x1bOS"),
+    (Key::F5, "\x1b[15~"),
+    (Key::F6, "\x1b[17~"),
+    (Key::F7, "\x1b[18~"),
+    (Key::F8, "\x1b[19~"),
+    (Key::F9, "\x1b[20~"),
+    (Key::F10, "\x1b[21~"),
+    (Key::F11, "\x1b[23~"),
+    (Key::F12, "\x1b[24~"),
+    (Key::F13, "\x1b[25~"),
+    (Key::F14, "\x1b[26~"),
+    (Key::F15, "\x1b[28~"),
+    (Key::F16, "\x1b[29~"),
+    (Key::F17, "\x1b[31~"),
+    (Key::F18, "\x1b[32~"),
+    (Key::F19, "\x1b[33~"),
+    (Key::F20, "\x1b[34~"),
+    (Key::F21, "\x1b[57384u"),
+    (Key::F22, "\x1b[57385u"),
+    (Key::F23, "\x1b[57386u"),
+    (Key::F24, "\x1b[57387u"),
+    (Key::Up, "\x1b[A"),
+    (Key::Down, "\x1b[B"),
+    (Key::Left, "\x1b[D"),
+    (Key::Right, "\x1b[C"),
+    (Key::KeypadDivide, "\x1b[57410u"),
+    (Key::KeypadMultiply, "\x1b[57411u"),
+    (Key::KeypadSubtract, "\x1b[57412u"),
+    (Key::KeypadAdd, "\x1b[57413u"),
+    (Key::KeypadEnter, "\x1b[57414u"),
+    (Key::Keypad1, "\x1b[57400u"),
+    (Key::Keypad2, "\x1b[57401u"),
+    (Key::Keypad3, "\x1b[57402u"),
+    (Key::Keypad4, "\x1b[57403u"),
+    (Key::Keypad5, "\x1b[57404u"),
+    (Key::Keypad6, "\x1b[57405u"),
+    (Key::Keypad7, "\x1b[57406u"),
+    (Key::Keypad8, "\x1b[57407u"),
+    (Key::Keypad9, "\x1b[57408u"),
+    (Key::Keypad0, "\x1b[57399u"),
+    (Key::KeypadDecimal, "\x1b[57409u"),
+    (Key::KeypadEqual, "\x1b[57415u"),
+    (Key::Mute, "\x1b[57440u"),
+    (Key::VolumeUp, "\x1b[57439u"),
+    (Key::VolumeDown, "\x1b[57438u"),
+    (Key::PlayPause, "\x1b[57430u"),
+    (Key::Stop, "\x1b[57432u"),
+    (Key::NextTrack, "\x1b[57435u"),
+    (Key::PreviousTrack, "\x1b[57436u"),
+    (Key::LeftCtrl, "\x1b[57442u"),
+    (Key::LeftShift, "\x1b[57441u"),
+    (Key::LeftAlt, "\x1b[57443u"),
+    (Key::LeftGui, "\x1b[57444u"),
+    (Key::RightCtrl, "\x1b[57448u"),
+    (Key::RightShift, "\x1b[57447u"),
+    (Key::RightAlt, "\x1b[57449u"),
+    (Key::RightGui, "\x1b[57450u"),
+];
+
+/// Represents a key going down or up.
+///
+/// ```text``` is the text the key typed, already taking the modifiers,
+/// keyboard layout and dead keys into account, or ```None``` for keys which
+/// don't type anything, like arrows, dead keys, or keys used as shortcuts
+/// with Ctrl, Alt or Super. On macOS, Option types characters as it
+/// usually does, as does AltGr on Windows. Text is also sent on its own as
+/// ```Event::Text``` for typing into widgets.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{Event, Key, KeyEvent, Modifiers, Parser};
+/// let mut parser = Parser::new();
+///
+/// let events = parser.feed(b"A");
+/// let press = KeyEvent {
+///     key: Key::A,
+///     down: true,
+///     modifiers: Modifiers::SHIFT,
+///     repeat: false,
+///     text: Some("A".to_string()),
+/// };
+/// assert!(events.contains(&Event::Key(press)));
+/// assert!(events.contains(&Event::Text("A".to_string())));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    /// Whether the key went down, as opposed to up
+    pub down: bool,
+    pub modifiers: Modifiers,
+    /// Whether the key is going down again because it is being held
+    pub repeat: bool,
+    pub text: Option<String>,
+}
+
+impl KeyEvent {
+    /// Creates an event for a key going down or up with no modifiers held
+    /// and no text
+    pub fn new(key: Key, down: bool) -> Self {
+        Self {
+            key,
+            down,
+            modifiers: Modifiers::NONE,
+            repeat: false,
+            text: None,
+        }
+    }
+}
+
+/// How long a ```Sequence``` waits for its next key by default
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Modifier names, in the order chords are written with
+const MODIFIER_NAMES: [(Modifiers, &str); 4] = [
+    (Modifiers::CTRL, "Ctrl"),
+    (Modifiers::ALT, "Alt"),
+    (Modifiers::SHIFT, "Shift"),
+    (Modifiers::SUPER, "Super"),
+];
+
+/// Represents a key pressed with exactly a set of modifiers held, like
+/// ```Ctrl+Shift+P```
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{Chord, Key, KeyEvent, Modifiers};
+/// let chord: Chord = "Ctrl+Shift+P".parse().unwrap();
+/// assert_eq!(chord, Chord::new(Key::P, Modifiers::CTRL | Modifiers::SHIFT));
+///
+/// let event = KeyEvent {
+///     modifiers: Modifiers::CTRL | Modifiers::SHIFT,
+///     ..KeyEvent::new(Key::P, true)
+/// };
+/// assert!(chord.matches(&event));
+/// assert!(!chord.matches(&KeyEvent::new(Key::P, true)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl Chord {
+    pub fn new(key: Key, modifiers: Modifiers) -> Self {
+        Self { key, modifiers }
+    }
+
+    /// Returns whether an event is the key of the chord going down, with
+    /// only the modifiers of the chord held
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        event.down && event.key == self.key && event.modifiers == self.modifiers
+    }
+}
+
+impl From<Key> for Chord {
+    fn from(key: Key) -> Self {
+        Self::new(key, Modifiers::NONE)
+    }
+}
+
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (modifier, name) in MODIFIER_NAMES {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
             }
         }
+        write!(f, "{}", self.key)
     }
 }
 
-#[cfg(windows)]
-use std::ptr::null_mut;
-use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
-use winapi::shared::windef::HHOOK;
-use winapi::um::winuser::{
-    CallNextHookEx, GetMessageW, SetWindowsHookExW, KBDLLHOOKSTRUCT, WH_KEYBOARD_LL, WM_KEYDOWN,
-    WM_KEYUP,
-};
+impl std::str::FromStr for Chord {
+    type Err = &'static str;
 
-unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
-    if code >= 0 {
-        let kb_struct = *(lparam as *const KBDLLHOOKSTRUCT);
+    /// Reads a chord from modifier names and a key joined by ```+```
+    ///
+    /// The key is either its name, or a character typed by it, in which
+    /// case Shift is added when the character needs it.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = match text.rsplit_once('+') {
+            // A chord of only the plus key
+            Some((modifiers, "")) => (modifiers.strip_suffix('+').unwrap_or(modifiers), "+"),
+            Some(split) => split,
+            None => ("", text),
+        };
 
-        let key = Key::from_code(kb_struct.vkCode as u8);
-        match key {
-            Some(key) => match key {
-                Key::Unknown => {}
-                key => match wparam as UINT {
-                    WM_KEYUP => {
-                        KEYS.get().unwrap().lock().unwrap().retain(|k| k != &key);
-
-                        match CALLBACK.get().unwrap().lock().unwrap().as_ref() {
-                            Some(func) => func(key, false),
-                            _ => {}
-                        }
-                    }
-                    WM_KEYDOWN => {
-                        KEYS.get().unwrap().lock().unwrap().push(key.clone());
+        let mut chord = match key.parse::<Key>() {
+            Ok(key) => Chord::from(key),
+            Err(error) => {
+                let mut characters = key.chars();
+                let (Some(character), None) = (characters.next(), characters.next()) else {
+                    return Err(error);
+                };
+                let (key, shift) = char_key(character).ok_or(error)?;
+                Chord::new(
+                    key,
+                    if shift {
+                        Modifiers::SHIFT
+                    } else {
+                        Modifiers::NONE
+                    },
+                )
+            }
+        };
 
-                        match CALLBACK.get().unwrap().lock().unwrap().as_ref() {
-                            Some(func) => func(key, true),
-                            _ => {}
-                        }
-                    }
-                    _ => {}
-                },
-            },
-            _ => {}
+        for name in modifiers.split('+').filter(|name| !name.is_empty()) {
+            let (modifier, _) = MODIFIER_NAMES
+                .iter()
+                .find(|(_, other)| other.eq_ignore_ascii_case(name))
+                .ok_or("Unknown modifier name")?;
+            chord.modifiers.insert(*modifier);
         }
+
+        Ok(chord)
     }
+}
 
-    CallNextHookEx(null_mut(), code, wparam, lparam)
+/// Detects chords pressed one after another, like ```g g``` in vim or a
+/// fighting game combo.
+///
+/// Key events are given to ```feed()```, which returns ```true``` once the
+/// last chord is pressed. Releases, repeats and modifier keys are ignored,
+/// and the sequence starts over when the next chord takes longer than the
+/// timeout to arrive.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{Key, KeyEvent, Sequence};
+/// let mut sequence: Sequence = "g g".parse().unwrap();
+///
+/// assert!(!sequence.feed(&KeyEvent::new(Key::G, true)));
+/// assert!(!sequence.feed(&KeyEvent::new(Key::G, false)));
+/// assert!(sequence.feed(&KeyEvent::new(Key::G, true)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    chords: Vec<Chord>,
+    timeout: Duration,
+    recent: Vec<KeyEvent>,
+    last: Option<Instant>,
 }
 
-/// Get the current set of keys being pressed
-pub fn keys() -> Vec<Key> {
-    KEYS.get().unwrap().lock().unwrap().clone()
+impl Sequence {
+    pub fn new(chords: Vec<Chord>) -> Self {
+        Self {
+            chords,
+            timeout: SEQUENCE_TIMEOUT,
+            recent: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Sets how long to wait for each chord after the first
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn chords(&self) -> &[Chord] {
+        &self.chords
+    }
+
+    /// Returns how many chords of the sequence have been pressed so far
+    pub fn progress(&self) -> usize {
+        (1..=self.recent.len().min(self.chords.len()))
+            .rev()
+            .find(|len| {
+                self.recent[self.recent.len() - len..]
+                    .iter()
+                    .zip(&self.chords)
+                    .all(|(event, chord)| chord.matches(event))
+            })
+            .unwrap_or(0)
+    }
+
+    /// Forgets the chords pressed so far
+    pub fn reset(&mut self) {
+        self.recent.clear();
+        self.last = None;
+    }
+
+    /// Takes a key event, returning whether it completed the sequence
+    pub fn feed(&mut self, event: &KeyEvent) -> bool {
+        self.feed_at(event, Instant::now())
+    }
+
+    /// Takes a key event which happened at the given time, returning
+    /// whether it completed the sequence
+    pub fn feed_at(&mut self, event: &KeyEvent, time: Instant) -> bool {
+        if !event.down || event.repeat || modifier_bit(event.key).is_some() {
+            return false;
+        }
+
+        if self
+            .last
+            .is_some_and(|last| time.saturating_duration_since(last) > self.timeout)
+        {
+            self.recent.clear();
+        }
+        self.last = Some(time);
+
+        self.recent.push(event.clone());
+        if self.recent.len() > self.chords.len() {
+            self.recent.remove(0);
+        }
+
+        // Only keep the events which still match the start of the sequence
+        let progress = self.progress();
+        self.recent.drain(..self.recent.len() - progress);
+
+        if !self.chords.is_empty() && progress == self.chords.len() {
+            self.reset();
+            return true;
+        }
+        false
+    }
 }
 
-/// Set the keyboard callback
-pub fn set_callback(callback: Option<Box<dyn Fn(Key, bool) + Send>>) {
-    *CALLBACK.get().unwrap().lock().unwrap() = callback;
+impl std::str::FromStr for Sequence {
+    type Err = &'static str;
+
+    /// Reads a sequence from chords separated by spaces
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let chords = text
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Chord>, _>>()?;
+        if chords.is_empty() {
+            return Err("Empty key sequence");
+        }
+
+        Ok(Self::new(chords))
+    }
 }
 
-/// Run the keyboard thread for listening to inputs
-pub fn run() -> Result<(), &'static str> {
-    KEYS.get_or_init(|| Mutex::new(Vec::new()));
-    CALLBACK.get_or_init(|| Mutex::new(None));
+/// Represents something which happened to the terminal, the keyboard or a
+/// gamepad.
+///
+/// Events are queued up to be taken with ```poll()``` or ```try_iter()```,
+/// and copies are sent to every channel returned by ```subscribe()```.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Key(KeyEvent),
+    /// Text typed by a key, or entered through an input method
+    Text(String),
+    Mouse(MouseEvent),
+    /// The terminal window was resized to the given width and height
+    Resize(usize, usize),
+    /// Text was pasted into the terminal
+    Paste(String),
+    FocusGained,
+    FocusLost,
+    /// A gamepad was plugged in, and given the id used by
+    /// ```gamepad::get()```
+    GamepadConnected(usize),
+    /// The gamepad with the given id was unplugged
+    GamepadDisconnected(usize),
+    /// Nothing happened before ```poll()``` timed out
+    Tick,
+    /// Reading the terminal or input devices failed, so no more input will
+    /// be read from them
+    Error(String),
+}
 
-    #[cfg(unix)]
-    unsafe {
-        std::thread::spawn(move || {
-            // Create the HID Manager
-            let manager = IOHIDManagerCreate(kCFAllocatorDefault, kIOHIDManagerOptionNone);
-            if manager.is_null() {
-                return Err("Failed to create IOHIDManager.");
-            }
+/// What the mouse did in a ```MouseEvent```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseKind {
+    Press,
+    Release,
+    /// Moved while a button is held
+    Drag,
+    /// Moved with no buttons held
+    Move,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+}
 
-            // Set device matching to keyboards
-            let matching_dict = IOServiceMatching(kIOHIDDeviceKey);
-            IOHIDManagerSetDeviceMatching(manager, matching_dict);
+/// Mouse button involved in a ```MouseEvent```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    /// No button, when moving or scrolling
+    None,
+    Left,
+    Middle,
+    Right,
+    Back,
+    Forward,
+}
 
-            // Register the input callback
-            IOHIDManagerRegisterInputValueCallback(manager, input_value_callback, ptr::null_mut());
+/// Represents the mouse being used inside the terminal.
+///
+/// The terminal only reports these once mouse tracking has been enabled
+/// (see ```terminal::InitOptions::mouse()```). ```x``` and ```y``` are
+/// the column and row of the cell under the mouse, starting from 0 at the
+/// top left like ```terminal::display_raw()```. Terminals don't report
+/// Super being held with the mouse.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{Event, Modifiers, MouseButton, MouseEvent, MouseKind, Parser};
+/// let mut parser = Parser::new();
+///
+/// let events = parser.feed(b"\x1b[<16;5;3M");
+/// let click = MouseEvent {
+///     kind: MouseKind::Press,
+///     button: MouseButton::Left,
+///     x: 4,
+///     y: 2,
+///     modifiers: Modifiers::CTRL,
+/// };
+/// assert_eq!(events, vec![Event::Mouse(click)]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub kind: MouseKind,
+    pub button: MouseButton,
+    pub x: u16,
+    pub y: u16,
+    pub modifiers: Modifiers,
+}
 
-            // Schedule with the run loop
-            IOHIDManagerScheduleWithRunLoop(
-                manager,
-                CFRunLoop::get_current().as_concrete_TypeRef(),
-                kCFRunLoopDefaultMode,
-            );
+/// Decodes the bytes a terminal writes to stdin into key presses, mouse
+/// events, pastes and focus changes.
+///
+/// Terminals only report the characters and escape sequences produced by
+/// keys, not the physical keys themselves, so every key is reported as going
+/// down and immediately back up. Modifiers (Shift for uppercase letters and
+/// symbols, Ctrl for control characters, Alt for escape-prefixed keys) are
+/// reported as being held around the key they apply to.
+///
+/// Terminals which support the kitty keyboard protocol can also report keys
+/// being repeated and released (see ```terminal::init_with_keyboard()```).
+/// Once ```set_release_events()``` is enabled, keys reported this way stay
+/// down until the terminal reports their release, and modifiers stay down
+/// for as long as the terminal reports them as held.
+///
+/// Text pasted in bracketed paste mode is decoded as a single
+/// ```Event::Paste``` rather than keys, however many reads it arrives in.
+/// A paste whose end never arrives is sent by ```flush()```, or once it
+/// gets too long.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{Event, Key, KeyEvent, Parser};
+/// let mut parser = Parser::new();
+///
+/// let events = parser.feed(b"\x1b[A");
+/// assert_eq!(
+///     events,
+///     vec![
+///         Event::Key(KeyEvent::new(Key::Up, true)),
+///         Event::Key(KeyEvent::new(Key::Up, false)),
+///     ]
+/// );
+///
+/// assert!(parser.feed(b"\x1b[200~q\r").is_empty());
+/// assert!(parser.pasting());
+/// assert_eq!(
+///     parser.feed(b"!\x1b[201~\x1b[O"),
+///     vec![Event::Paste("q\n!".to_string()), Event::FocusLost]
+/// );
+/// ```
+#[derive(Default)]
+pub struct Parser {
+    buffer: Vec<u8>,
+    release_events: bool,
+    /// Modifier keys reported as held by kitty keyboard protocol events
+    held: Vec<Key>,
+    /// How many bytes of an unfinished paste were already searched for its
+    /// end marker
+    paste_scanned: usize,
+}
 
-            // Open the HID Manager
-            let result = IOHIDManagerOpen(manager, kIOHIDOptionsTypeNone);
-            if result != kIOReturnSuccess {
-                eprintln!("Failed to open IOHIDManager. Error code: {}", result);
-                CFRelease(manager as CFTypeRef);
-                return Err("Failed to open IOHIDManager");
-            }
+/// What happened to a key in a single report
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Pressed and released, because the terminal can't report releases
+    Tap,
+    Press,
+    Repeat,
+    Release,
+}
 
-            println!("Listening for keyboard events. Press keys to see output.");
+/// Result of parsing the start of the input buffer
+enum Parsed {
+    /// A key, its modifiers, what happened to it and the text it typed,
+    /// along with how many bytes it took up
+    Key(usize, Key, u8, Action, Option<String>),
+    /// Text which doesn't correspond to any known key, along with how many
+    /// bytes it took up
+    Text(usize, String),
+    /// A mouse event, along with how many bytes it took up
+    Mouse(usize, MouseEvent),
+    /// A paste or focus change, along with how many bytes it took up
+    Event(usize, Event),
+    /// Bytes which don't represent any known key
+    Skip(usize),
+    /// The buffer ends partway through a sequence
+    Incomplete,
+}
 
-            // Start the run loop
-            CFRunLoop::run_current();
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-            // Clean up
-            CFRelease(manager as CFTypeRef);
+    /// Feeds bytes read from stdin into the parser, returning the decoded
+    /// events.
+    ///
+    /// Incomplete escape sequences are kept until more bytes arrive or
+    /// ```flush()``` is called.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(bytes);
 
-            Ok(())
-        });
+        let mut events = Vec::new();
+        let mut start = 0;
+        while start < self.buffer.len() {
+            if self.buffer[start..].starts_with(PASTE_START) {
+                match self.paste(start) {
+                    Some((len, event)) => {
+                        events.push(event);
+                        start += len;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+
+            match parse(&self.buffer[start..], self.release_events) {
+                Parsed::Key(len, key, modifiers, Action::Tap, text) => {
+                    press(&mut events, key, modifiers, text);
+                    start += len;
+                }
+                Parsed::Key(len, key, modifiers, action, text) => {
+                    self.event(&mut events, key, modifiers, action, text);
+                    start += len;
+                }
+                Parsed::Text(len, text) => {
+                    events.push(Event::Text(text));
+                    start += len;
+                }
+                Parsed::Mouse(len, event) => {
+                    events.push(Event::Mouse(event));
+                    start += len;
+                }
+                Parsed::Event(len, event) => {
+                    events.push(event);
+                    start += len;
+                }
+                Parsed::Skip(len) => start += len,
+                Parsed::Incomplete => break,
+            }
+        }
+        self.buffer.drain(..start);
+
+        events
     }
 
+    /// Sets whether the terminal reports key releases.
+    ///
+    /// This should match whether the kitty keyboard protocol was enabled
+    /// with ```terminal::KITTY_REPORT_EVENT_TYPES```, otherwise keys
+    /// reported by escape sequences either never go up or go up immediately.
+    pub fn set_release_events(&mut self, enabled: bool) {
+        self.release_events = enabled;
+    }
+
+    /// Returns ```true``` if the parser is holding on to an incomplete
+    /// escape sequence.
+    ///
+    /// A paste whose end hasn't arrived yet isn't pending, as the rest of
+    /// it can take much longer to arrive (see ```pasting()```).
+    pub fn pending(&self) -> bool {
+        !self.buffer.is_empty() && !self.pasting()
+    }
+
+    /// Returns ```true``` if the parser is partway through a bracketed
+    /// paste whose end marker hasn't arrived yet
+    pub fn pasting(&self) -> bool {
+        self.buffer.starts_with(PASTE_START)
+    }
+
+    /// Decodes whatever is left over from ```feed()```.
+    ///
+    /// A lone escape can't be told apart from the start of an escape
+    /// sequence until nothing follows it, so this should be called once
+    /// stdin has been quiet for a short while. A paste whose end hasn't
+    /// arrived is sent with the text received so far, so this should only
+    /// be called once stdin has been quiet for longer while pasting.
+    pub fn flush(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.pasting() {
+            events.push(Event::Paste(paste_text(&self.buffer[PASTE_START.len()..])));
+            self.buffer.clear();
+            self.paste_scanned = 0;
+            return events;
+        }
+        if !self.pending() {
+            return events;
+        }
+
+        match self.buffer.as_slice() {
+            [0x1b] => press(&mut events, Key::Escape, 0, None),
+            [0x1b, byte] => {
+                if let Some((key, modifiers)) = byte_key(*byte) {
+                    press(&mut events, key, modifiers | ALT, None);
+                }
+            }
+            _ => {}
+        }
+        self.buffer.clear();
+
+        events
+    }
+}
+
+impl Parser {
+    /// Decodes the bracketed paste at ```start``` in the buffer, returning
+    /// the paste and how many bytes it took up, or ```None``` if its end
+    /// hasn't arrived yet.
+    ///
+    /// Searching resumes where the last call left off, so a paste arriving
+    /// in many reads isn't searched from the start each time.
+    fn paste(&mut self, start: usize) -> Option<(usize, Event)> {
+        let text = &self.buffer[start + PASTE_START.len()..];
+
+        // Only a paste left unfinished by the last call starts the buffer,
+        // and its end marker may have been split between reads
+        let from = if start == 0 {
+            self.paste_scanned.saturating_sub(PASTE_END.len() - 1)
+        } else {
+            0
+        };
+        self.paste_scanned = 0;
+
+        let end = text[from..]
+            .windows(PASTE_END.len())
+            .position(|window| window == PASTE_END);
+        match end {
+            Some(end) => Some((
+                PASTE_START.len() + from + end + PASTE_END.len(),
+                Event::Paste(paste_text(&text[..from + end])),
+            )),
+            // Give up on the end marker rather than holding on to the paste
+            // forever
+            None if text.len() > MAX_PASTE_LEN => Some((
+                PASTE_START.len() + text.len(),
+                Event::Paste(paste_text(text)),
+            )),
+            None => {
+                self.paste_scanned = text.len();
+                None
+            }
+        }
+    }
+
+    /// Pushes a key reported with its own press, repeat or release
+    fn event(
+        &mut self,
+        events: &mut Vec<Event>,
+        key: Key,
+        modifiers: u8,
+        action: Action,
+        text: Option<String>,
+    ) {
+        let down = action != Action::Release;
+        let repeat = action == Action::Repeat;
+
+        // Modifier keys are only reported on their own when the terminal
+        // reports every key
+        if modifier_bit(key).is_some() {
+            self.held.retain(|held| *held != key);
+            if down {
+                self.held.push(key);
+            }
+            push_key(events, key, down, modifiers, repeat, None);
+            return;
+        }
+
+        // Bring the held modifiers in line with the ones reported with the key
+        for (bit, modifier) in MODIFIERS {
+            if modifiers & bit == 0 {
+                self.held.retain(|held| {
+                    if modifier_bit(*held) == Some(bit) {
+                        push_key(events, *held, false, modifiers, false, None);
+                        return false;
+                    }
+                    true
+                });
+            } else if !self
+                .held
+                .iter()
+                .any(|held| modifier_bit(*held) == Some(bit))
+            {
+                self.held.push(modifier);
+                push_key(events, modifier, true, modifiers, false, None);
+            }
+        }
+
+        push_key(events, key, down, modifiers, repeat, text);
+    }
+}
+
+/// Returns the modifier bit a key sets, if it is a modifier key
+fn modifier_bit(key: Key) -> Option<u8> {
+    match key {
+        Key::LeftShift | Key::RightShift => Some(SHIFT),
+        Key::LeftAlt | Key::RightAlt => Some(ALT),
+        Key::LeftCtrl | Key::RightCtrl => Some(CTRL),
+        Key::LeftGui | Key::RightGui => Some(SUPER),
+        _ => None,
+    }
+}
+
+/// Pushes a key going down then up, surrounded by its modifiers
+fn press(events: &mut Vec<Event>, key: Key, modifiers: u8, text: Option<String>) {
+    let held: Vec<Key> = MODIFIERS
+        .into_iter()
+        .filter(|(bit, _)| modifiers & bit != 0)
+        .map(|(_, key)| key)
+        .collect();
+
+    for modifier in &held {
+        push_key(events, *modifier, true, modifiers, false, None);
+    }
+    push_key(events, key, true, modifiers, false, text);
+    push_key(events, key, false, modifiers, false, None);
+    for modifier in held.iter().rev() {
+        push_key(events, *modifier, false, modifiers, false, None);
+    }
+}
+
+/// Pushes a key event, followed by the text it typed if it went down
+fn push_key(
+    events: &mut Vec<Event>,
+    key: Key,
+    down: bool,
+    modifiers: u8,
+    repeat: bool,
+    text: Option<String>,
+) {
+    let text = text.filter(|_| down);
+    events.push(Event::Key(KeyEvent {
+        key,
+        down,
+        modifiers: Modifiers::from_bits(modifiers),
+        repeat,
+        text: text.clone(),
+    }));
+
+    if let Some(text) = text {
+        events.push(Event::Text(text));
+    }
+}
+
+/// Parses a single key from the start of ```bytes```
+///
+/// ```release_events``` is whether keys reported by escape sequences will
+/// later be reported as released.
+fn parse(bytes: &[u8], release_events: bool) -> Parsed {
+    match bytes {
+        [] | [0x1b] | [0x1b, b'O'] => Parsed::Incomplete,
+
+        // Linux console function keys
+        [0x1b, b'[', b'['] => Parsed::Incomplete,
+        [0x1b, b'[', b'[', final_byte, ..] => {
+            let key = match final_byte {
+                b'A' => Key::F1,
+                b'B' => Key::F2,
+                b'C' => Key::F3,
+                b'D' => Key::F4,
+                b'E' => Key::F5,
+                _ => return Parsed::Skip(4),
+            };
+            Parsed::Key(4, key, 0, Action::Tap, None)
+        }
+
+        // Focus reports
+        [0x1b, b'[', b'I', ..] => Parsed::Event(3, Event::FocusGained),
+        [0x1b, b'[', b'O', ..] => Parsed::Event(3, Event::FocusLost),
+
+        // Control Sequence Introducer
+        [0x1b, b'[', rest @ ..] => {
+            let Some(end) = rest.iter().position(|byte| (0x40..=0x7e).contains(byte)) else {
+                if rest.len() > MAX_SEQUENCE_LEN {
+                    return Parsed::Skip(bytes.len());
+                }
+                return Parsed::Incomplete;
+            };
+
+            // SGR mouse reports
+            if rest.first() == Some(&b'<') && matches!(rest[end], b'M' | b'm') {
+                return match mouse(&rest[1..end], rest[end] == b'm') {
+                    Some(event) => Parsed::Mouse(end + 3, event),
+                    None => Parsed::Skip(end + 3),
+                };
+            }
+
+            match csi(&rest[..end], rest[end], release_events) {
+                Some((key, modifiers, action, text)) => {
+                    Parsed::Key(end + 3, key, modifiers, action, text)
+                }
+                None => Parsed::Skip(end + 3),
+            }
+        }
+
+        // Single Shift Three
+        [0x1b, b'O', final_byte, ..] => {
+            let key = match final_byte {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                b'M' => Key::Enter,
+                b'P' => Key::F1,
+                b'Q' => Key::F2,
+                b'R' => Key::F3,
+                b'S' => Key::F4,
+                _ => return Parsed::Skip(3),
+            };
+            Parsed::Key(3, key, 0, Action::Tap, None)
+        }
+
+        // Escape followed by a key means Alt was held
+        [0x1b, rest @ ..] => match parse(rest, release_events) {
+            // Keys held with Alt are shortcuts rather than typing
+            Parsed::Key(len, key, modifiers, action, _) => {
+                Parsed::Key(len + 1, key, modifiers | ALT, action, None)
+            }
+            Parsed::Text(len, _) => Parsed::Skip(len + 1),
+            Parsed::Mouse(len, event) => Parsed::Mouse(len + 1, event),
+            Parsed::Event(len, event) => Parsed::Event(len + 1, event),
+            Parsed::Skip(len) => Parsed::Skip(len + 1),
+            Parsed::Incomplete => Parsed::Incomplete,
+        },
+
+        [byte, ..] if byte.is_ascii() => {
+            let text =
+                (byte.is_ascii_graphic() || *byte == b' ').then(|| (*byte as char).to_string());
+            match byte_key(*byte) {
+                Some((key, modifiers)) => Parsed::Key(1, key, modifiers, Action::Tap, text),
+                None => Parsed::Skip(1),
+            }
+        }
+
+        // Multi-byte UTF-8 characters don't correspond to any key on a US
+        // layout, but are still typed text
+        [byte, ..] => {
+            let len = (byte.leading_ones() as usize).clamp(1, 4);
+            if bytes.len() < len {
+                return Parsed::Incomplete;
+            }
+
+            match std::str::from_utf8(&bytes[..len]) {
+                Ok(text) => Parsed::Text(len, text.to_string()),
+                Err(_) => Parsed::Skip(len),
+            }
+        }
+    }
+}
+
+/// Converts the bytes between the markers of a bracketed paste into text,
+/// with the carriage returns terminals send for new lines turned into line
+/// feeds
+fn paste_text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+/// Converts the parameters of an SGR mouse report (```CSI < b;x;y M```,
+/// or ```m``` when a button is released) into a mouse event
+fn mouse(params: &[u8], release: bool) -> Option<MouseEvent> {
+    let params: Vec<u16> = std::str::from_utf8(params)
+        .ok()?
+        .split(';')
+        .map(|param| param.parse().ok())
+        .collect::<Option<_>>()?;
+    let [code, x, y] = params[..] else {
+        return None;
+    };
+
+    // The low bits are the button, the next ones the modifiers held, and
+    // the high ones say whether the mouse moved or the wheel was used
+    let modifiers = [(4, SHIFT), (8, ALT), (16, CTRL)]
+        .into_iter()
+        .filter(|(bit, _)| code & bit != 0)
+        .fold(0, |modifiers, (_, modifier)| modifiers | modifier);
+    let motion = code & 32 != 0;
+
+    let (kind, button) = match code & !(4 | 8 | 16 | 32) {
+        64 => (MouseKind::ScrollUp, MouseButton::None),
+        65 => (MouseKind::ScrollDown, MouseButton::None),
+        66 => (MouseKind::ScrollLeft, MouseButton::None),
+        67 => (MouseKind::ScrollRight, MouseButton::None),
+        3 if motion => (MouseKind::Move, MouseButton::None),
+        button => {
+            let button = match button {
+                0 => MouseButton::Left,
+                1 => MouseButton::Middle,
+                2 => MouseButton::Right,
+                128 => MouseButton::Back,
+                129 => MouseButton::Forward,
+                _ => MouseButton::None,
+            };
+            let kind = if motion {
+                MouseKind::Drag
+            } else if release {
+                MouseKind::Release
+            } else {
+                MouseKind::Press
+            };
+            (kind, button)
+        }
+    };
+
+    Some(MouseEvent {
+        kind,
+        button,
+        x: x.saturating_sub(1),
+        y: y.saturating_sub(1),
+        modifiers: Modifiers::from_bits(modifiers),
+    })
+}
+
+/// Returns the text typed by a key reported by the kitty keyboard protocol
+///
+/// The text is reported as a third parameter with
+/// ```terminal::KITTY_REPORT_TEXT```, otherwise it is worked out from the
+/// key code, and the shifted key when
+/// ```terminal::KITTY_REPORT_ALTERNATE_KEYS``` is enabled.
+fn kitty_text(
+    params: &[Vec<Option<u32>>],
+    code: u32,
+    modifiers: u8,
+    action: Action,
+) -> Option<String> {
+    if action == Action::Release {
+        return None;
+    }
+
+    if let Some(text) = params.get(2) {
+        return text
+            .iter()
+            .map(|code| char::from_u32((*code)?))
+            .collect::<Option<String>>()
+            .filter(|text| !text.is_empty());
+    }
+
+    // Keys held with Ctrl, Alt or Super are shortcuts rather than typing,
+    // and codes in the private use area are functional keys
+    if modifiers & (CTRL | ALT | SUPER) != 0 || (57344..=63743).contains(&code) {
+        return None;
+    }
+
+    let character = char::from_u32(code).filter(|character| !character.is_control())?;
+    let character = match (modifiers & SHIFT != 0, params[0].get(1).copied().flatten()) {
+        (false, _) => character,
+        (true, Some(shifted)) => char::from_u32(shifted)?,
+        (true, None) if character.is_lowercase() => character.to_uppercase().next()?,
+        (true, None) => return None,
+    };
+
+    Some(character.to_string())
+}
+
+/// Converts the parameters and final byte of a CSI sequence into a key
+fn csi(
+    params: &[u8],
+    final_byte: u8,
+    release_events: bool,
+) -> Option<(Key, u8, Action, Option<String>)> {
+    // Sequences with a private marker are replies to queries, not keys
+    if params.first().is_some_and(|byte| b"<=>?".contains(byte)) {
+        return None;
+    }
+
+    // Parameters are separated by semicolons, and may have sub-parameters
+    // separated by colons
+    let params: Vec<Vec<Option<u32>>> = params
+        .split(|byte| *byte == b';')
+        .map(|param| {
+            param
+                .split(|byte| *byte == b':')
+                .map(|sub| std::str::from_utf8(sub).ok()?.parse().ok())
+                .collect()
+        })
+        .collect();
+    let param = |index: usize, sub: usize| params.get(index)?.get(sub).copied().flatten();
+
+    // Modifiers are sent as one more than the bitmask, followed by the kind
+    // of event when the kitty keyboard protocol reports them
+    let modifiers = param(1, 0)
+        .map(|param| param.saturating_sub(1) as u8 & (SHIFT | ALT | CTRL | SUPER))
+        .unwrap_or(0);
+    let action = match param(1, 1) {
+        Some(2) => Action::Repeat,
+        Some(3) => Action::Release,
+        Some(_) => Action::Press,
+        None if release_events => Action::Press,
+        None => Action::Tap,
+    };
+
+    let key = match final_byte {
+        b'u' => {
+            let code = param(0, 0)?;
+            let (key, shift) = kitty_key(code)?;
+            let text = kitty_text(&params, code, modifiers, action);
+            return Some((
+                key,
+                if shift { modifiers | SHIFT } else { modifiers },
+                action,
+                text,
+            ));
+        }
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'P' => Key::F1,
+        b'Q' => Key::F2,
+        b'R' => Key::F3,
+        b'S' => Key::F4,
+        b'Z' => return Some((Key::Tab, modifiers | SHIFT, action, None)),
+        b'~' => match param(0, 0)? {
+            1 | 7 => Key::Home,
+            2 => Key::Insert,
+            3 => Key::Delete,
+            4 | 8 => Key::End,
+            5 => Key::PageUp,
+            6 => Key::PageDown,
+            11 => Key::F1,
+            12 => Key::F2,
+            13 => Key::F3,
+            14 => Key::F4,
+            15 => Key::F5,
+            17 => Key::F6,
+            18 => Key::F7,
+            19 => Key::F8,
+            20 => Key::F9,
+            21 => Key::F10,
+            23 => Key::F11,
+            24 => Key::F12,
+            25 => Key::F13,
+            26 => Key::F14,
+            28 => Key::F15,
+            29 => Key::F16,
+            31 => Key::F17,
+            32 => Key::F18,
+            33 => Key::F19,
+            34 => Key::F20,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some((key, modifiers, action, None))
+}
+
+/// Converts a key code from the kitty keyboard protocol into a key, and
+/// whether Shift has to be held to type it
+///
+/// See https://sw.kovidgoyal.net/kitty/keyboard-protocol/#functional-key-definitions
+fn kitty_key(code: u32) -> Option<(Key, bool)> {
+    let key = match code {
+        8 | 127 => Key::Backspace,
+        9 => Key::Tab,
+        13 => Key::Enter,
+        27 => Key::Escape,
+        57358 => Key::CapsLock,
+        57359 => Key::ScrollLock,
+        57360 => Key::NumLock,
+        57361 => Key::PrintScreen,
+        57362 => Key::Pause,
+        57363 => Key::Menu,
+        57376..=57387 => FUNCTION_KEYS[(code - 57376) as usize + 12],
+        57399 => Key::Keypad0,
+        57400..=57408 => KEYPAD_DIGITS[(code - 57400) as usize],
+        57409 => Key::KeypadDecimal,
+        57410 => Key::KeypadDivide,
+        57411 => Key::KeypadMultiply,
+        57412 => Key::KeypadSubtract,
+        57413 => Key::KeypadAdd,
+        57414 => Key::KeypadEnter,
+        57415 => Key::KeypadEqual,
+        57430 => Key::PlayPause,
+        57432 => Key::Stop,
+        57435 => Key::NextTrack,
+        57436 => Key::PreviousTrack,
+        57438 => Key::VolumeDown,
+        57439 => Key::VolumeUp,
+        57440 => Key::Mute,
+        57441 => Key::LeftShift,
+        57442 => Key::LeftCtrl,
+        57443 => Key::LeftAlt,
+        57444 => Key::LeftGui,
+        57447 => Key::RightShift,
+        57448 => Key::RightCtrl,
+        57449 => Key::RightAlt,
+        57450 => Key::RightGui,
+        _ => return char_key(char::from_u32(code)?),
+    };
+
+    Some((key, false))
+}
+
+/// Converts a single byte of terminal input into a key and its modifiers
+fn byte_key(byte: u8) -> Option<(Key, u8)> {
+    match byte {
+        0x00 => Some((Key::Space, CTRL)),
+        0x09 => Some((Key::Tab, 0)),
+        b'\r' | b'\n' => Some((Key::Enter, 0)),
+        0x08 | 0x7f => Some((Key::Backspace, 0)),
+        0x1b => Some((Key::Escape, 0)),
+        0x01..=0x1a => Some((Key::from_code(byte - 0x01 + Key::A as u8)?, CTRL)),
+        0x1c => Some((Key::Backslash, CTRL)),
+        0x1d => Some((Key::RightBracket, CTRL)),
+        0x1e => Some((Key::N6, CTRL | SHIFT)),
+        0x1f => Some((Key::Hyphen, CTRL | SHIFT)),
+        _ => {
+            let (key, shift) = char_key(byte as char)?;
+            Some((key, if shift { SHIFT } else { 0 }))
+        }
+    }
+}
+
+/// Converts a character into the key which types it on a US keyboard, and
+/// whether Shift has to be held to type it
+fn char_key(character: char) -> Option<(Key, bool)> {
+    let key = match character {
+        'a'..='z' => (
+            Key::from_code(character as u8 - b'a' + Key::A as u8)?,
+            false,
+        ),
+        'A'..='Z' => (Key::from_code(character as u8 - b'A' + Key::A as u8)?, true),
+        '1'..='9' => (
+            Key::from_code(character as u8 - b'1' + Key::N1 as u8)?,
+            false,
+        ),
+        '0' => (Key::N0, false),
+        '!' => (Key::N1, true),
+        '@' => (Key::N2, true),
+        '#' => (Key::N3, true),
+        '$' => (Key::N4, true),
+        '%' => (Key::N5, true),
+        '^' => (Key::N6, true),
+        '&' => (Key::N7, true),
+        '*' => (Key::N8, true),
+        '(' => (Key::N9, true),
+        ')' => (Key::N0, true),
+        ' ' => (Key::Space, false),
+        '-' => (Key::Hyphen, false),
+        '_' => (Key::Hyphen, true),
+        '=' => (Key::Equal, false),
+        '+' => (Key::Equal, true),
+        '[' => (Key::LeftBracket, false),
+        '{' => (Key::LeftBracket, true),
+        ']' => (Key::RightBracket, false),
+        '}' => (Key::RightBracket, true),
+        '\\' => (Key::Backslash, false),
+        '|' => (Key::Backslash, true),
+        ';' => (Key::Semicolon, false),
+        ':' => (Key::Semicolon, true),
+        '\'' => (Key::Apostrophe, false),
+        '"' => (Key::Apostrophe, true),
+        '`' => (Key::Grave, false),
+        '~' => (Key::Grave, true),
+        ',' => (Key::Comma, false),
+        '<' => (Key::Comma, true),
+        '.' => (Key::Period, false),
+        '>' => (Key::Period, true),
+        '/' => (Key::Slash, false),
+        '?' => (Key::Slash, true),
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+#[cfg(target_os = "macos")]
+use {
+    core_foundation::{
+        base::{kCFAllocatorDefault, CFRelease, CFTypeRef},
+        data::{CFDataGetBytePtr, CFDataRef},
+        runloop::{kCFRunLoopDefaultMode, CFRunLoop},
+        string::CFStringRef,
+    },
+    io_kit_sys::{
+        hid::{
+            base::IOHIDValueRef,
+            element::{IOHIDElementGetUsage, IOHIDElementGetUsagePage},
+            keys::{kIOHIDDeviceKey, kIOHIDOptionsTypeNone},
+            manager::{
+                kIOHIDManagerOptionNone, IOHIDManagerCreate, IOHIDManagerOpen,
+                IOHIDManagerRegisterInputValueCallback, IOHIDManagerScheduleWithRunLoop,
+                IOHIDManagerSetDeviceMatching,
+            },
+            value::{IOHIDValueGetElement, IOHIDValueGetIntegerValue},
+        },
+        ret::kIOReturnSuccess,
+        IOServiceMatching,
+    },
+};
+
+#[cfg(target_os = "macos")]
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    static kTISPropertyUnicodeKeyLayoutData: CFStringRef;
+
+    fn TISCopyCurrentKeyboardLayoutInputSource() -> CFTypeRef;
+    fn TISGetInputSourceProperty(source: CFTypeRef, key: CFStringRef) -> CFTypeRef;
+    fn LMGetKbdType() -> u8;
+    fn UCKeyTranslate(
+        layout: *const std::ffi::c_void,
+        virtual_key_code: u16,
+        key_action: u16,
+        modifier_key_state: u32,
+        keyboard_type: u32,
+        key_translate_options: u32,
+        dead_key_state: *mut u32,
+        max_string_length: usize,
+        actual_string_length: *mut usize,
+        unicode_string: *mut u16,
+    ) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventSourceFlagsState(state_id: i32) -> u64;
+}
+
+/// Event source state of the keyboard itself, for ```CGEventSourceFlagsState()```
+#[cfg(target_os = "macos")]
+const HID_SYSTEM_STATE: i32 = 1;
+
+/// Flag set by ```CGEventSourceFlagsState()``` while Caps Lock is on
+#[cfg(target_os = "macos")]
+const ALPHA_SHIFT_FLAG: u64 = 0x0001_0000;
+
+/// Modifier bits given to ```UCKeyTranslate()```, which are Carbon's
+/// modifier flags shifted right by 8
+#[cfg(target_os = "macos")]
+const UC_SHIFT: u32 = 0x02;
+#[cfg(target_os = "macos")]
+const UC_ALPHA_LOCK: u32 = 0x04;
+#[cfg(target_os = "macos")]
+const UC_OPTION: u32 = 0x08;
+
+/// Virtual key codes which ```UCKeyTranslate()``` takes for the keys which
+/// type text
+#[cfg(target_os = "macos")]
+const MAC_KEY_CODES: [(Key, u16); 64] = [
+    (Key::A, 0x00),
+    (Key::S, 0x01),
+    (Key::D, 0x02),
+    (Key::F, 0x03),
+    (Key::H, 0x04),
+    (Key::G, 0x05),
+    (Key::Z, 0x06),
+    (Key::X, 0x07),
+    (Key::C, 0x08),
+    (Key::V, 0x09),
+    (Key::B, 0x0B),
+    (Key::Q, 0x0C),
+    (Key::W, 0x0D),
+    (Key::E, 0x0E),
+    (Key::R, 0x0F),
+    (Key::Y, 0x10),
+    (Key::T, 0x11),
+    (Key::N1, 0x12),
+    (Key::N2, 0x13),
+    (Key::N3, 0x14),
+    (Key::N4, 0x15),
+    (Key::N6, 0x16),
+    (Key::N5, 0x17),
+    (Key::Equal, 0x18),
+    (Key::N9, 0x19),
+    (Key::N7, 0x1A),
+    (Key::Hyphen, 0x1B),
+    (Key::N8, 0x1C),
+    (Key::N0, 0x1D),
+    (Key::RightBracket, 0x1E),
+    (Key::O, 0x1F),
+    (Key::U, 0x20),
+    (Key::LeftBracket, 0x21),
+    (Key::I, 0x22),
+    (Key::P, 0x23),
+    (Key::L, 0x25),
+    (Key::J, 0x26),
+    (Key::Apostrophe, 0x27),
+    (Key::K, 0x28),
+    (Key::Semicolon, 0x29),
+    (Key::Backslash, 0x2A),
+    (Key::Comma, 0x2B),
+    (Key::Slash, 0x2C),
+    (Key::N, 0x2D),
+    (Key::M, 0x2E),
+    (Key::Period, 0x2F),
+    (Key::Space, 0x31),
+    (Key::Grave, 0x32),
+    (Key::KeypadDecimal, 0x41),
+    (Key::KeypadMultiply, 0x43),
+    (Key::KeypadAdd, 0x45),
+    (Key::KeypadDivide, 0x4B),
+    (Key::KeypadSubtract, 0x4E),
+    (Key::KeypadEqual, 0x51),
+    (Key::Keypad0, 0x52),
+    (Key::Keypad1, 0x53),
+    (Key::Keypad2, 0x54),
+    (Key::Keypad3, 0x55),
+    (Key::Keypad4, 0x56),
+    (Key::Keypad5, 0x57),
+    (Key::Keypad6, 0x58),
+    (Key::Keypad7, 0x59),
+    (Key::Keypad8, 0x5B),
+    (Key::Keypad9, 0x5C),
+];
+
+/// Dead key typed last, as kept by ```UCKeyTranslate()``` until the key it
+/// goes on is pressed
+#[cfg(target_os = "macos")]
+static DEAD_KEY_STATE: OnceLock<Mutex<u32>> = OnceLock::new();
+
+/// Returns the text typed by a key going down, using the current keyboard
+/// layout
+#[cfg(target_os = "macos")]
+unsafe fn typed_text(key: Key, modifiers: Modifiers) -> Option<String> {
+    // Control and Command are used for shortcuts, while Option types
+    // characters like AltGr does elsewhere
+    if modifiers.contains(Modifiers::CTRL) || modifiers.contains(Modifiers::SUPER) {
+        return None;
+    }
+    let code = find_code(&MAC_KEY_CODES, key)?;
+
+    let mut state = 0;
+    if modifiers.contains(Modifiers::SHIFT) {
+        state |= UC_SHIFT;
+    }
+    if modifiers.contains(Modifiers::ALT) {
+        state |= UC_OPTION;
+    }
+    if CGEventSourceFlagsState(HID_SYSTEM_STATE) & ALPHA_SHIFT_FLAG != 0 {
+        state |= UC_ALPHA_LOCK;
+    }
+
+    let source = TISCopyCurrentKeyboardLayoutInputSource();
+    if source.is_null() {
+        return None;
+    }
+
+    let layout = TISGetInputSourceProperty(source, kTISPropertyUnicodeKeyLayoutData) as CFDataRef;
+    let mut buffer = [0u16; 8];
+    let mut length = 0;
+    let status = if layout.is_null() {
+        -1
+    } else {
+        let mut dead_key_state = DEAD_KEY_STATE.get_or_init(|| Mutex::new(0)).lock().unwrap();
+
+        // A dead key types nothing, and is combined with the next key
+        UCKeyTranslate(
+            CFDataGetBytePtr(layout).cast(),
+            code,
+            0, // kUCKeyActionDown
+            state,
+            LMGetKbdType() as u32,
+            0,
+            &mut *dead_key_state,
+            buffer.len(),
+            &mut length,
+            buffer.as_mut_ptr(),
+        )
+    };
+    CFRelease(source);
+
+    if status != 0 || length == 0 {
+        return None;
+    }
+
+    let text = String::from_utf16_lossy(&buffer[..length]);
+    (!text.chars().any(char::is_control)).then_some(text)
+}
+
+/// Input callback for input thread
+#[cfg(target_os = "macos")]
+extern "C" fn input_value_callback(
+    _context: *mut std::ffi::c_void,
+    _result: i32,
+    _sender: *mut std::ffi::c_void,
+    value: IOHIDValueRef,
+) {
+    unsafe {
+        // Get the element associated with the value
+        let element = IOHIDValueGetElement(value);
+
+        // Get the usage page and usage
+        let usage_page = IOHIDElementGetUsagePage(element);
+        let usage = IOHIDElementGetUsage(element);
+
+        // Get the value (pressed or released)
+        let pressed = IOHIDValueGetIntegerValue(value);
+
+        if let Some(key) = Key::from_hid(usage_page, usage) {
+            let mut event = held_event(key, pressed != 0);
+            if event.down {
+                event.text = typed_text(key, event.modifiers);
+            }
+            send_typed(event);
+        }
+    }
+}
+
+#[cfg(windows)]
+use std::ptr::null_mut;
+#[cfg(windows)]
+use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+#[cfg(windows)]
+use winapi::shared::windef::HHOOK;
+#[cfg(windows)]
+use winapi::um::{
+    winnls::{NormalizationC, NormalizeString},
+    winuser::{
+        CallNextHookEx, GetForegroundWindow, GetKeyState, GetKeyboardLayout, GetMessageW,
+        GetWindowThreadProcessId, SetWindowsHookExW, ToUnicodeEx, KBDLLHOOKSTRUCT, LLKHF_EXTENDED,
+        VK_CAPITAL, VK_CONTROL, VK_MENU, VK_SHIFT, WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP,
+        WM_SYSKEYDOWN, WM_SYSKEYUP,
+    },
+};
+
+/// Flag which stops ```ToUnicodeEx()``` from changing the keyboard state,
+/// so that dead keys still work in the focused window
+#[cfg(windows)]
+const TO_UNICODE_KEEP_STATE: UINT = 0b100;
+
+/// Accents typed by dead keys, and the combining marks which put them on
+/// the next character
+#[cfg(windows)]
+const DEAD_KEY_ACCENTS: [(char, char); 15] = [
+    ('`', '\u{0300}'),
+    ('\u{00B4}', '\u{0301}'),
+    ('\'', '\u{0301}'),
+    ('^', '\u{0302}'),
+    ('~', '\u{0303}'),
+    ('\u{00AF}', '\u{0304}'),
+    ('\u{02D8}', '\u{0306}'),
+    ('\u{02D9}', '\u{0307}'),
+    ('\u{00A8}', '\u{0308}'),
+    ('"', '\u{0308}'),
+    ('\u{02DA}', '\u{030A}'),
+    ('\u{02DD}', '\u{030B}'),
+    ('\u{02C7}', '\u{030C}'),
+    ('\u{00B8}', '\u{0327}'),
+    ('\u{02DB}', '\u{0328}'),
+];
+
+/// Accent typed by the last dead key, waiting for the key it goes on
+#[cfg(windows)]
+static DEAD_KEY: OnceLock<Mutex<Option<char>>> = OnceLock::new();
+
+#[cfg(windows)]
+unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 {
+        let kb_struct = *(lparam as *const KBDLLHOOKSTRUCT);
+
+        let key = match Key::from_virtual_key(kb_struct.vkCode) {
+            Some(Key::Enter) if kb_struct.flags & LLKHF_EXTENDED != 0 => Some(Key::KeypadEnter),
+            key => key,
+        };
+        if let Some(key) = key {
+            // Keys pressed while Alt is held are system keys
+            match wparam as UINT {
+                WM_KEYDOWN | WM_SYSKEYDOWN => {
+                    let mut event = held_event(key, true);
+                    event.text = typed_text(&kb_struct, event.modifiers);
+                    send_typed(event);
+                }
+                WM_KEYUP | WM_SYSKEYUP => send_typed(held_event(key, false)),
+                _ => {}
+            }
+        }
+    }
+
+    CallNextHookEx(null_mut(), code, wparam, lparam)
+}
+
+/// Returns the text typed by a key going down, using the keyboard layout
+/// of the focused window
+#[cfg(windows)]
+unsafe fn typed_text(kb_struct: &KBDLLHOOKSTRUCT, modifiers: Modifiers) -> Option<String> {
+    // Keys used as shortcuts with Alt or the Windows key don't type
+    // anything, while AltGr is sent as Ctrl+Alt
+    let alt_gr = modifiers.contains(Modifiers::CTRL | Modifiers::ALT);
+    if modifiers.contains(Modifiers::SUPER) || (modifiers.contains(Modifiers::ALT) && !alt_gr) {
+        return None;
+    }
+
+    // The hook runs before the focused window sees the key, so the state
+    // of the modifiers comes from the keys being held
+    let mut state = [0u8; 256];
+    for key in keys() {
+        let generic = match modifier_bit(key) {
+            Some(SHIFT) => VK_SHIFT,
+            Some(CTRL) => VK_CONTROL,
+            Some(ALT) => VK_MENU,
+            _ => continue,
+        };
+        state[generic as usize] = 0x80;
+        if let Some(code) = key.to_virtual_key() {
+            state[code as usize] = 0x80;
+        }
+    }
+    state[VK_CAPITAL as usize] = (GetKeyState(VK_CAPITAL) & 1) as u8;
+
+    let thread = GetWindowThreadProcessId(GetForegroundWindow(), null_mut());
+    let mut buffer = [0u16; 8];
+    let count = ToUnicodeEx(
+        kb_struct.vkCode,
+        kb_struct.scanCode,
+        state.as_ptr(),
+        buffer.as_mut_ptr(),
+        buffer.len() as i32,
+        TO_UNICODE_KEEP_STATE,
+        GetKeyboardLayout(thread),
+    );
+    let text =
+        String::from_utf16_lossy(&buffer[..(count.unsigned_abs() as usize).min(buffer.len())]);
+
+    let mut dead_key = DEAD_KEY.get_or_init(|| Mutex::new(None)).lock().unwrap();
+    let text = match (count, dead_key.take()) {
+        // Keys which type nothing, like modifiers, leave a dead key waiting
+        (0, accent) => {
+            *dead_key = accent;
+            return None;
+        }
+        // Dead keys type nothing until the next key, unless pressed twice
+        (count, None) if count < 0 => {
+            *dead_key = text.chars().next();
+            return None;
+        }
+        (count, Some(accent)) if count < 0 => format!("{accent}{text}"),
+        (_, Some(accent)) => compose(accent, &text),
+        (_, None) => text,
+    };
+
+    (!text.chars().any(char::is_control)).then_some(text)
+}
+
+/// Puts the accent typed by a dead key on the text typed after it. Like in
+/// other windows, both are typed when there's no such character, and the
+/// accent on its own is typed by Space.
+#[cfg(windows)]
+fn compose(accent: char, text: &str) -> String {
+    let mark = DEAD_KEY_ACCENTS
+        .iter()
+        .find(|(other, _)| *other == accent)
+        .map(|(_, mark)| *mark);
+
+    match mark {
+        _ if text == " " => accent.to_string(),
+        Some(mark) => {
+            let source: Vec<u16> = format!("{text}{mark}").encode_utf16().collect();
+            let mut composed = [0u16; 8];
+            let count = unsafe {
+                NormalizeString(
+                    NormalizationC,
+                    source.as_ptr(),
+                    source.len() as i32,
+                    composed.as_mut_ptr(),
+                    composed.len() as i32,
+                )
+            };
+
+            let composed = String::from_utf16_lossy(&composed[..count.max(0) as usize]);
+            if composed.chars().count() == 1 {
+                composed
+            } else {
+                format!("{accent}{text}")
+            }
+        }
+        None => format!("{accent}{text}"),
+    }
+}
+
+/// Creates an event for a key going down or up, with the modifiers in the
+/// set of keys being pressed
+#[cfg(any(target_os = "macos", windows))]
+fn held_event(key: Key, down: bool) -> KeyEvent {
+    let keys = keys();
+    let held = keys
+        .iter()
+        .chain(down.then_some(&key))
+        .filter(|held| **held != key || down)
+        .filter_map(|held| modifier_bit(*held))
+        .fold(0, |modifiers, bit| modifiers | bit);
+
+    KeyEvent {
+        modifiers: Modifiers::from_bits(held),
+        repeat: down && keys.contains(&key),
+        ..KeyEvent::new(key, down)
+    }
+}
+
+/// Sends a key event read by a hook backend, followed by the text it typed
+#[cfg(any(target_os = "macos", windows))]
+fn send_typed(event: KeyEvent) {
+    let text = event.text.clone();
+    if send(event) {
+        if let Some(text) = text {
+            push(Event::Text(text));
+        }
+    }
+}
+
+/// Sends a key event read by a backend, replacing the repeats from the
+/// operating system with synthesized ones when ```set_repeat()``` was
+/// given a rate
+///
+/// Returns whether the event was sent.
+pub(crate) fn send(event: KeyEvent) -> bool {
+    let (repeater, condvar) = repeats();
+    if let Some(repeater) = repeater.lock().unwrap().as_mut() {
+        if !repeater.feed(&event) {
+            return false;
+        }
+        condvar.notify_all();
+    }
+
+    deliver(event);
+    true
+}
+
+/// Updates the set of keys being pressed, notifies the callback and queues
+/// the key as an event
+fn deliver(event: KeyEvent) {
+    let (key, down) = (event.key, event.down);
+
+    let mut keys = KEYS.get_or_init(|| Mutex::new(Vec::new())).lock().unwrap();
+    if !down {
+        keys.retain(|k| k != &key);
+    } else if !keys.contains(&key) {
+        keys.push(key);
+    }
+    drop(keys);
+
+    if let Some(func) = CALLBACK
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap()
+        .as_ref()
+    {
+        func(key, down);
+    }
+
+    push(Event::Key(event));
+}
+
+/// Sends an event through the same callbacks as events read by the
+/// backends, without synthesizing repeats for keys
+pub(crate) fn emit(event: Event) {
+    match event {
+        Event::Key(event) => deliver(event),
+        Event::Mouse(event) => {
+            if let Some(func) = MOUSE_CALLBACK
+                .get_or_init(|| Mutex::new(None))
+                .lock()
+                .unwrap()
+                .as_ref()
+            {
+                func(event);
+            }
+            push(Event::Mouse(event));
+        }
+        event => push(event),
+    }
+}
+
+/// Sends repeats of the keys being held once they're due, for as long as
+/// the program runs
+fn repeat_keys() {
+    let (repeater, condvar) = repeats();
+    let mut state = repeater.lock().unwrap();
+
+    loop {
+        let now = Instant::now();
+        let due = state
+            .as_mut()
+            .map_or_else(Vec::new, |repeater| repeater.due(now));
+
+        if !due.is_empty() {
+            drop(state);
+            for event in due {
+                let text = event.text.clone();
+                deliver(event);
+                if let Some(text) = text {
+                    push(Event::Text(text));
+                }
+            }
+            state = repeater.lock().unwrap();
+            continue;
+        }
+
+        state = match state.as_ref().and_then(Repeater::next) {
+            Some(next) => {
+                condvar
+                    .wait_timeout(state, next.saturating_duration_since(now))
+                    .unwrap()
+                    .0
+            }
+            None => condvar.wait(state).unwrap(),
+        };
+    }
+}
+
+/// Sends the events read from the terminal
+#[cfg(target_os = "linux")]
+fn dispatch(events: Vec<Event>) {
+    // Whether the last key was a repeat replaced by a synthesized one, whose
+    // text is dropped along with it
+    let mut replaced = false;
+
+    for event in events {
+        match event {
+            // Keys are read from the keyboards themselves instead
+            Event::Key(_) if EVDEV.load(Ordering::SeqCst) => {}
+            Event::Key(event) => {
+                replaced = !send(event);
+                continue;
+            }
+            Event::Text(_) if replaced => {}
+            event => emit(event),
+        }
+        replaced = false;
+    }
+}
+
+/// Reads key presses and mouse events from the terminal's stdin
+///
+/// The terminal must be in raw mode (see ```terminal::init()```), otherwise
+/// input only arrives once Enter is pressed.
+#[cfg(target_os = "linux")]
+fn read_stdin() -> Result<(), &'static str> {
+    let mut parser = Parser::new();
+    let mut buffer = [0u8; 1024];
+
+    // Keys typed while the terminal was being probed
+    parser.set_release_events(terminal::keyboard_flags() & terminal::KITTY_REPORT_EVENT_TYPES != 0);
+    dispatch(parser.feed(&terminal::caps::take_input()));
+
+    loop {
+        parser.set_release_events(
+            terminal::keyboard_flags() & terminal::KITTY_REPORT_EVENT_TYPES != 0,
+        );
+
+        // Give the rest of an unfinished escape sequence a moment to arrive,
+        // and the rest of a paste longer
+        let timeout = if parser.pasting() {
+            PASTE_TIMEOUT_MS
+        } else if parser.pending() {
+            ESCAPE_TIMEOUT_MS
+        } else {
+            -1
+        };
+
+        let mut poll_fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+            0 => {
+                dispatch(parser.flush());
+                continue;
+            }
+            result if result < 0 => {
+                if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err("Failed to poll stdin");
+            }
+            _ => {}
+        }
+
+        let read = unsafe {
+            libc::read(
+                libc::STDIN_FILENO,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        };
+
+        if read < 0 {
+            if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err("Failed to read stdin");
+        }
+
+        // Stdin was closed, so nothing more will arrive
+        if read == 0 {
+            dispatch(parser.flush());
+            return Ok(());
+        }
+
+        dispatch(parser.feed(&buffer[..read as usize]));
+    }
+}
+
+/// Reads keys straight from keyboards through the Linux input subsystem,
+/// which unlike the terminal reports keys going up as well as down.
+///
+/// Keyboards are read wherever the terminal's focus is, so this is meant
+/// for kiosk-style machines where the program owns the whole screen.
+/// Reading ```/dev/input/event*``` needs root or membership of the
+/// ```input``` group.
+#[cfg(target_os = "linux")]
+pub mod evdev {
+    use std::{
+        collections::HashMap,
+        fs::{File, OpenOptions},
+        io::{ErrorKind, Read},
+        mem,
+        os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+        path::PathBuf,
+        sync::atomic::Ordering,
+        time::{Duration, Instant},
+    };
+
+    use super::{modifier_bit, Event, Key, KeyEvent, Modifiers};
+
+    /// Directory holding the input devices
+    const DEVICE_DIR: &str = "/dev/input";
+
+    /// How often to look for devices which were plugged in
+    const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Event type for keys and buttons
+    pub(crate) const EV_KEY: u16 = 0x01;
+    /// Event type for absolute axes
+    pub(crate) const EV_ABS: u16 = 0x03;
+    /// Highest code of any event type, from ```linux/input-event-codes.h```
+    const CODE_MAX: usize = 0x2ff;
+    /// Key codes a device needs to report to count as a keyboard
+    const KEYBOARD_KEYS: [u16; 4] = [1, 16, 28, 57];
+
+    /// Size of ```struct input_event```, whose time field depends on the
+    /// platform
+    const EVENT_SIZE: usize = mem::size_of::<libc::input_event>();
+
+    /// Returns an ```ioctl``` request which reads ```len``` bytes from an
+    /// input device
+    pub(crate) const fn ioc_read(number: u64, len: usize) -> u64 {
+        const IOC_READ: u64 = 2;
+        (IOC_READ << 30) | ((len as u64) << 16) | ((b'E' as u64) << 8) | number
+    }
+
+    /// Takes the complete ```input_event``` structs from the bytes read so
+    /// far, returning their type, code and value
+    ///
+    /// Structs may be split across reads, so the remainder of one is kept
+    /// in the buffer.
+    pub(crate) fn decode(buffer: &mut Vec<u8>, bytes: &[u8]) -> Vec<(u16, u16, i32)> {
+        buffer.extend_from_slice(bytes);
+
+        let len = buffer.len() - buffer.len() % EVENT_SIZE;
+        buffer
+            .drain(..len)
+            .collect::<Vec<u8>>()
+            .chunks_exact(EVENT_SIZE)
+            .map(|chunk| {
+                // The type, code and value follow the time, whatever its
+                // size
+                let fields = &chunk[EVENT_SIZE - 8..];
+                (
+                    u16::from_ne_bytes([fields[0], fields[1]]),
+                    u16::from_ne_bytes([fields[2], fields[3]]),
+                    i32::from_ne_bytes([fields[4], fields[5], fields[6], fields[7]]),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns whether an input device reports every one of the codes for
+    /// an event type
+    pub(crate) fn has_codes(file: &File, event_type: u16, codes: &[u16]) -> bool {
+        let mut bits = [0u8; CODE_MAX / 8 + 1];
+        let request = ioc_read(0x20 + event_type as u64, bits.len());
+        if unsafe { libc::ioctl(file.as_raw_fd(), request as _, bits.as_mut_ptr()) } < 0 {
+            return false;
+        }
+
+        codes
+            .iter()
+            .all(|code| bits[*code as usize / 8] & (1 << (code % 8)) != 0)
+    }
+
+    /// An open input device, read by ```read_devices()```
+    pub(crate) trait Device: Sized {
+        /// Keeps a device open if it's the kind being read
+        fn open(file: File) -> Option<Self>;
+
+        fn file(&self) -> &File;
+
+        /// Handles bytes read from the device
+        fn read(&mut self, bytes: &[u8]);
+
+        /// Handles the device being unplugged
+        fn remove(self);
+    }
+
+    /// Starts reading devices on a separate thread, sending
+    /// ```Event::Error``` if reading them fails
+    pub(crate) fn spawn_reader<T: Device + Send + 'static>(devices: HashMap<PathBuf, T>) {
+        std::thread::spawn(move || {
+            if let Err(error) = read_devices(devices) {
+                super::push(Event::Error(error.to_string()));
+            }
+        });
+    }
+
+    /// Opens the devices in ```/dev/input``` which aren't open yet,
+    /// returning whether any of them could be read, even ones which
+    /// weren't the kind being read
+    pub(crate) fn open_devices<T: Device>(devices: &mut HashMap<PathBuf, T>) -> bool {
+        let Ok(entries) = std::fs::read_dir(DEVICE_DIR) else {
+            return false;
+        };
+
+        let mut readable = false;
+
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            let is_event = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"));
+            if !is_event || devices.contains_key(&path) {
+                continue;
+            }
+
+            let Ok(file) = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+                .open(&path)
+            else {
+                continue;
+            };
+
+            readable = true;
+            if let Some(device) = T::open(file) {
+                devices.insert(path, device);
+            }
+        }
+
+        readable
+    }
+
+    /// Reads every device until an error occurs, opening devices as
+    /// they're plugged in
+    fn read_devices<T: Device>(mut devices: HashMap<PathBuf, T>) -> Result<(), &'static str> {
+        let mut buffer = [0u8; EVENT_SIZE * 64];
+        let mut scanned = Instant::now();
+
+        loop {
+            // Devices which keep sending events mustn't stop new ones from
+            // being found
+            if scanned.elapsed() >= RESCAN_INTERVAL {
+                open_devices(&mut devices);
+                scanned = Instant::now();
+            }
+
+            let paths: Vec<PathBuf> = devices.keys().cloned().collect();
+            let mut poll_fds: Vec<libc::pollfd> = paths
+                .iter()
+                .map(|path| libc::pollfd {
+                    fd: devices[path].file().as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+
+            let result = unsafe {
+                libc::poll(
+                    poll_fds.as_mut_ptr(),
+                    poll_fds.len() as libc::nfds_t,
+                    RESCAN_INTERVAL
+                        .saturating_sub(scanned.elapsed())
+                        .as_millis() as i32,
+                )
+            };
+            if result < 0 {
+                if std::io::Error::last_os_error().kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err("Failed to poll input devices");
+            }
+
+            for (path, poll_fd) in paths.iter().zip(&poll_fds) {
+                if poll_fd.revents == 0 {
+                    continue;
+                }
+
+                let device = devices.get_mut(path).unwrap();
+                let mut file = device.file();
+                match file.read(&mut buffer) {
+                    Ok(read) if read > 0 => device.read(&buffer[..read]),
+                    Err(error)
+                        if matches!(
+                            error.kind(),
+                            ErrorKind::WouldBlock | ErrorKind::Interrupted
+                        ) => {}
+                    // The device was unplugged
+                    _ => devices.remove(path).unwrap().remove(),
+                }
+            }
+        }
+    }
+
+    /// Converts the ```input_event``` structs read from an input device
+    /// into events.
+    ///
+    /// Structs may be split across reads, so the remainder of one is
+    /// kept until the next call to ```feed()```. Keys are held until
+    /// their release is read, and set the modifiers of the events which
+    /// follow.
+    #[derive(Debug, Default)]
+    pub struct Parser {
+        buffer: Vec<u8>,
+        held: Vec<Key>,
+    }
+
+    impl Parser {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Parses bytes read from an input device, returning the key
+        /// events they completed
+        pub fn feed(&mut self, bytes: &[u8]) -> Vec<Event> {
+            decode(&mut self.buffer, bytes)
+                .into_iter()
+                .filter(|(event_type, _, _)| *event_type == EV_KEY)
+                .filter_map(|(_, code, value)| self.key(Key::from_evdev(code)?, value))
+                .collect()
+        }
+
+        /// Returns the keys currently held down
+        pub fn held(&self) -> &[Key] {
+            &self.held
+        }
+
+        /// Creates an event for a key going up (0), down (1) or being
+        /// repeated (2)
+        fn key(&mut self, key: Key, value: i32) -> Option<Event> {
+            let down = match value {
+                0 => false,
+                1 | 2 => true,
+                _ => return None,
+            };
+
+            let repeat = value == 2 || (down && self.held.contains(&key));
+            if down && !repeat {
+                self.held.push(key);
+            } else if !down {
+                self.held.retain(|held| *held != key);
+            }
+
+            let modifiers = self
+                .held
+                .iter()
+                .filter_map(|held| modifier_bit(*held))
+                .fold(0, |modifiers, bit| modifiers | bit);
+
+            Some(Event::Key(KeyEvent {
+                modifiers: Modifiers::from_bits(modifiers),
+                repeat,
+                ..KeyEvent::new(key, down)
+            }))
+        }
+    }
+
+    /// A keyboard being read
+    struct Keyboard {
+        file: File,
+        parser: Parser,
+    }
+
+    impl Device for Keyboard {
+        /// Keeps devices which report the keys of a keyboard, rather than
+        /// only a few buttons like a power button or a mouse
+        fn open(file: File) -> Option<Self> {
+            has_codes(&file, EV_KEY, &KEYBOARD_KEYS).then(|| Self {
+                file,
+                parser: Parser::new(),
+            })
+        }
+
+        fn file(&self) -> &File {
+            &self.file
+        }
+
+        fn read(&mut self, bytes: &[u8]) {
+            for event in self.parser.feed(bytes) {
+                if let Event::Key(event) = event {
+                    super::send(event);
+                }
+            }
+        }
+
+        /// Releases the keys held on the keyboard
+        fn remove(self) {
+            for key in self.parser.held() {
+                super::send(KeyEvent::new(*key, false));
+            }
+        }
+    }
+
+    /// Starts reading keys from every keyboard on a separate thread, in
+    /// place of the keys read from the terminal.
+    ///
+    /// The terminal is still read for text, mouse and other events, so
+    /// ```keyboard::run()``` should be called as well.
+    /// ```Event::Error``` is sent if the keyboards can no longer be read.
+    pub fn run() -> Result<(), &'static str> {
+        let mut devices: HashMap<PathBuf, Keyboard> = HashMap::new();
+        open_devices(&mut devices);
+        if devices.is_empty() {
+            return Err("No readable keyboards in /dev/input");
+        }
+
+        super::EVDEV.store(true, Ordering::SeqCst);
+        spawn_reader(devices);
+
+        Ok(())
+    }
+}
+
+/// Represents how keys repeat while held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repeat {
+    /// Time between a key going down and its first repeat
+    pub delay: Duration,
+    /// Time between each repeat after the first
+    pub interval: Duration,
+}
+
+/// Synthesizes repeats for keys held down, from keys going down and up.
+///
+/// Key events are given to ```feed()```, which drops the repeats sent by the
+/// operating system or terminal, and ```due()``` returns the repeats to
+/// send in their place. Modifier keys don't repeat. This is what
+/// ```set_repeat()``` uses for the keys read by the backends.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{Key, KeyEvent, Repeat, Repeater};
+/// # use std::time::{Duration, Instant};
+/// let mut repeater = Repeater::new(Repeat {
+///     delay: Duration::from_millis(500),
+///     interval: Duration::from_millis(50),
+/// });
+///
+/// let start = Instant::now();
+/// assert!(repeater.feed_at(&KeyEvent::new(Key::A, true), start));
+/// assert_eq!(repeater.next(), Some(start + Duration::from_millis(500)));
+///
+/// let repeats = repeater.due(start + Duration::from_millis(500));
+/// assert_eq!(repeats.len(), 1);
+/// assert!(repeats[0].repeat);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Repeater {
+    repeat: Repeat,
+    held: Vec<(KeyEvent, Instant)>,
+}
+
+impl Repeater {
+    pub fn new(repeat: Repeat) -> Self {
+        Self {
+            repeat,
+            held: Vec::new(),
+        }
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    /// Takes a key event, returning whether it should be sent on, which it
+    /// isn't if it's a repeat
+    pub fn feed(&mut self, event: &KeyEvent) -> bool {
+        self.feed_at(event, Instant::now())
+    }
+
+    /// Takes a key event which happened at the given time, returning
+    /// whether it should be sent on
+    pub fn feed_at(&mut self, event: &KeyEvent, time: Instant) -> bool {
+        if event.repeat {
+            return false;
+        }
+
+        self.held.retain(|(held, _)| held.key != event.key);
+        if event.down && modifier_bit(event.key).is_none() {
+            self.held.push((event.clone(), time + self.repeat.delay));
+        }
+        true
+    }
+
+    /// Returns when the next repeat is due, if any key is held
+    pub fn next(&self) -> Option<Instant> {
+        self.held.iter().map(|(_, next)| *next).min()
+    }
+
+    /// Returns the repeats due by the given time.
+    ///
+    /// Keys repeat at most once per call, so repeats which were missed
+    /// aren't sent all at once.
+    pub fn due(&mut self, now: Instant) -> Vec<KeyEvent> {
+        let interval = self.repeat.interval;
+        self.held
+            .iter_mut()
+            .filter(|(_, next)| *next <= now)
+            .map(|(event, next)| {
+                *next += interval;
+                if *next <= now {
+                    *next = now + interval;
+                }
+
+                KeyEvent {
+                    repeat: true,
+                    ..event.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+fn repeats() -> &'static (Mutex<Option<Repeater>>, Condvar) {
+    REPEATS.get_or_init(|| (Mutex::new(None), Condvar::new()))
+}
+
+/// Sets how keys repeat while held down, or ```None``` to pass on the
+/// repeats sent by the operating system or terminal
+///
+/// Repeats are synthesized from keys going down and up, so they are only
+/// sent for keys read from backends which report keys going up: evdev,
+/// IOKit, Win32, and terminals using the kitty keyboard protocol with
+/// ```terminal::KITTY_REPORT_EVENT_TYPES```. Modifier keys don't repeat.
+pub fn set_repeat(repeat: Option<Repeat>) {
+    static THREAD: Once = Once::new();
+
+    let (repeater, condvar) = repeats();
+    *repeater.lock().unwrap() = repeat.map(Repeater::new);
+    condvar.notify_all();
+
+    if repeat.is_some() {
+        THREAD.call_once(|| {
+            std::thread::spawn(repeat_keys);
+        });
+    }
+}
+
+/// Returns how keys repeat while held down, as set by ```set_repeat()```
+pub fn repeat() -> Option<Repeat> {
+    repeats().0.lock().unwrap().as_ref().map(Repeater::repeat)
+}
+
+/// Get the current set of keys being pressed
+pub fn keys() -> Vec<Key> {
+    KEYS.get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap()
+        .clone()
+}
+
+/// Set the keyboard callback
+///
+/// The callback is called from the keyboard thread while it holds a lock,
+/// so games are usually better off reading events with ```poll()``` or
+/// ```try_iter()``` once per frame.
+pub fn set_callback(callback: Option<Callback>) {
+    *CALLBACK.get_or_init(|| Mutex::new(None)).lock().unwrap() = callback;
+}
+
+/// Returns the event queue and the condition signalled when it is pushed to
+fn queue() -> &'static (Mutex<VecDeque<Event>>, Condvar) {
+    QUEUE.get_or_init(|| (Mutex::new(VecDeque::new()), Condvar::new()))
+}
+
+/// Adds an event to the queue and sends it to every subscriber.
+///
+/// The keyboard thread and the terminal push their own events, but this can
+/// also be used to inject events, for example ones replayed from a file.
+pub fn push(event: Event) {
+    // Channels whose receiver was dropped are no longer needed, and ones
+    // which aren't being read are dropped before they use up memory
+    SUBSCRIBERS
+        .get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap()
+        .retain(|subscriber| subscriber.try_send(event.clone()).is_ok());
+
+    let (queue, ready) = queue();
+    let mut queue = queue.lock().unwrap();
+    if queue.len() >= QUEUE_CAPACITY {
+        queue.pop_front();
+    }
+    queue.push_back(event);
+    ready.notify_all();
+}
+
+/// Waits up to ```timeout``` for an event and takes it off the queue,
+/// returning ```Event::Tick``` if none arrived in time.
+///
+/// # Example
+/// ```no_run
+/// # use a5c11::keyboard::{self, Event};
+/// # use std::time::Duration;
+/// loop {
+///     match keyboard::poll(Duration::from_millis(16)) {
+///         Event::Tick => { /* update and draw the next frame */ }
+///         event => println!("{event:?}"),
+///     }
+/// }
+/// ```
+pub fn poll(timeout: Duration) -> Event {
+    let (queue, ready) = queue();
+    let queue = queue.lock().unwrap();
+    let (mut queue, _) = ready
+        .wait_timeout_while(queue, timeout, |queue| queue.is_empty())
+        .unwrap();
+
+    queue.pop_front().unwrap_or(Event::Tick)
+}
+
+/// Returns an iterator taking every event off the queue without waiting.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{self, Event, Key};
+/// keyboard::push(Event::Text("a".to_string()));
+///
+/// for event in keyboard::try_iter() {
+///     assert_eq!(event, Event::Text("a".to_string()));
+/// }
+/// ```
+pub fn try_iter() -> impl Iterator<Item = Event> {
+    std::iter::from_fn(|| queue().0.lock().unwrap().pop_front())
+}
+
+/// Returns a channel which receives a copy of every event from now on.
+///
+/// Each subscriber sees every event, independently of the queue read by
+/// ```poll()``` and ```try_iter()``` and of other subscribers. Dropping the
+/// receiver unsubscribes it. A subscriber which falls more than 1024 events
+/// behind is unsubscribed, and disconnects once it has read the events
+/// sent before that.
+///
+/// # Example
+/// ```
+/// # use a5c11::keyboard::{self, Event};
+/// let events = keyboard::subscribe();
+/// keyboard::push(Event::FocusGained);
+/// assert_eq!(events.try_recv(), Ok(Event::FocusGained));
+///
+/// for _ in 0..2000 {
+///     keyboard::push(Event::FocusLost);
+/// }
+/// assert_eq!(events.try_iter().count(), 1024);
+/// assert!(events.recv().is_err());
+/// ```
+pub fn subscribe() -> Receiver<Event> {
+    let (sender, receiver) = sync_channel(QUEUE_CAPACITY);
+    SUBSCRIBERS
+        .get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap()
+        .push(sender);
+    receiver
+}
+
+/// Set the mouse callback
+///
+/// Mouse events are only read from the terminal, so this is only called on
+/// Linux, once mouse tracking has been enabled with
+/// ```terminal::InitOptions::mouse()```.
+pub fn set_mouse_callback(callback: Option<MouseCallback>) {
+    *MOUSE_CALLBACK
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap() = callback;
+}
+
+/// Run the keyboard thread for listening to inputs
+///
+/// On Linux, input is read from the terminal until stdin is closed, after
+/// which no more events arrive. ```Event::Error``` is sent if reading it
+/// fails.
+pub fn run() -> Result<(), &'static str> {
+    KEYS.get_or_init(|| Mutex::new(Vec::new()));
+    CALLBACK.get_or_init(|| Mutex::new(None));
+    MOUSE_CALLBACK.get_or_init(|| Mutex::new(None));
+
+    #[cfg(target_os = "macos")]
+    unsafe {
+        std::thread::spawn(move || {
+            // Create the HID Manager
+            let manager = IOHIDManagerCreate(kCFAllocatorDefault, kIOHIDManagerOptionNone);
+            if manager.is_null() {
+                return Err("Failed to create IOHIDManager.");
+            }
+
+            // Set device matching to keyboards
+            let matching_dict = IOServiceMatching(kIOHIDDeviceKey);
+            IOHIDManagerSetDeviceMatching(manager, matching_dict);
+
+            // Register the input callback
+            IOHIDManagerRegisterInputValueCallback(manager, input_value_callback, ptr::null_mut());
+
+            // Schedule with the run loop
+            IOHIDManagerScheduleWithRunLoop(
+                manager,
+                CFRunLoop::get_current().as_concrete_TypeRef(),
+                kCFRunLoopDefaultMode,
+            );
+
+            // Open the HID Manager
+            let result = IOHIDManagerOpen(manager, kIOHIDOptionsTypeNone);
+            if result != kIOReturnSuccess {
+                eprintln!("Failed to open IOHIDManager. Error code: {}", result);
+                CFRelease(manager as CFTypeRef);
+                return Err("Failed to open IOHIDManager");
+            }
+
+            println!("Listening for keyboard events. Press keys to see output.");
+
+            // Start the run loop
+            CFRunLoop::run_current();
+
+            // Clean up
+            CFRelease(manager as CFTypeRef);
+
+            Ok(())
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    std::thread::spawn(|| {
+        if let Err(error) = read_stdin() {
+            push(Event::Error(error.to_string()));
+        }
+    });
+
     #[cfg(windows)]
     {
         std::thread::spawn(|| {
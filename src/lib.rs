@@ -1,3 +1,7 @@
+pub mod action;
 pub mod core;
+#[cfg(target_os = "linux")]
+pub mod gamepad;
 pub mod keyboard;
+pub mod replay;
 pub mod sprites;
@@ -1,17 +1,17 @@
-use core::terminal::rgb;
-use core::{rand::range, Sprite};
+use a5c11::core::terminal::rgb;
+use a5c11::core::{self, rand::range, Sprite};
+use a5c11::keyboard::{self, Event, Key, KeyEvent};
 use std::{thread::sleep, time::Duration};
 
-use sprites::{Checkerboard, Firework};
-
-mod core;
-mod keyboard;
-mod sprites;
+use a5c11::sprites::{Checkerboard, Firework};
 
 fn main() {
-    let handle = core::terminal::init().unwrap();
+    let _handle = core::terminal::InitOptions::new()
+        .signals(true)
+        .init()
+        .unwrap();
     let (width, height) = core::terminal::size().unwrap();
-    // keyboard::run().unwrap();
+    keyboard::run().unwrap();
 
     // let adjusted_width: usize = height / 2;
     // let adjusted_height: usize = height / 2;
@@ -36,10 +36,9 @@ fn main() {
     vec.push(Box::new(checkerboard));
     vec.push(Box::new(firework));
 
-    println!("Test");
-    keyboard::set_callback(Some(Box::new(|key, down| {
-        println!("key: {key:?}, down: {down}");
-    })));
+    // Output processing is off in raw mode, so newlines need a carriage
+    // return to get back to the start of the line
+    print!("Test\r\n");
 
     loop {
         // // Read one byte from stdin (should be non-blocking and immediate in raw mode)
@@ -67,11 +66,22 @@ fn main() {
         // core::terminal::display_raw(&page).unwrap();
 
         //core::terminal::display(&mut vec).unwrap();
-        // sleep(Duration::from_millis(100));
+        let escape = keyboard::try_iter().any(|event| {
+            matches!(
+                event,
+                Event::Key(KeyEvent {
+                    key: Key::Escape,
+                    ..
+                })
+            )
+        });
+        if escape {
+            break;
+        }
+
+        sleep(Duration::from_millis(100));
 
         // Print the raw key that was pressed (no echo in raw mode)
         // println!("You pressed: {}", input[0] as char);
     }
-
-    drop(handle);
 }
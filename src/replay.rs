@@ -0,0 +1,494 @@
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::RecvTimeoutError,
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    core::rand,
+    keyboard::{self, Event, Key, KeyEvent, Modifiers, MouseButton, MouseEvent, MouseKind},
+};
+
+/// Bytes every recording starts with
+const MAGIC: &[u8; 4] = b"A5RP";
+/// Version of the recording format
+const VERSION: u8 = 1;
+
+/// How often a recorder checks whether it was stopped
+const STOP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Mouse event kinds in the order they are numbered in recordings
+const MOUSE_KINDS: [MouseKind; 8] = [
+    MouseKind::Press,
+    MouseKind::Release,
+    MouseKind::Drag,
+    MouseKind::Move,
+    MouseKind::ScrollUp,
+    MouseKind::ScrollDown,
+    MouseKind::ScrollLeft,
+    MouseKind::ScrollRight,
+];
+
+/// Mouse buttons in the order they are numbered in recordings
+const MOUSE_BUTTONS: [MouseButton; 6] = [
+    MouseButton::None,
+    MouseButton::Left,
+    MouseButton::Middle,
+    MouseButton::Right,
+    MouseButton::Back,
+    MouseButton::Forward,
+];
+
+/// Represents input events along with when they happened, and the seed
+/// ```core::rand``` was given when they were recorded.
+///
+/// Recordings are saved as a header followed by one record per event,
+/// each starting with the time since the previous event in microseconds.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use a5c11::{keyboard::{Event, Key, KeyEvent}, replay::Recording};
+/// let recording = Recording {
+///     seed: 1234,
+///     events: vec![
+///         (Duration::from_millis(10), Event::Key(KeyEvent::new(Key::Escape, true))),
+///         (Duration::from_millis(20), Event::Resize(80, 24)),
+///     ],
+/// };
+///
+/// let bytes = recording.to_bytes();
+/// assert_eq!(Recording::from_bytes(&bytes), Ok(recording));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub seed: u64,
+    /// Events along with the time since the recording started
+    pub events: Vec<(Duration, Event)>,
+}
+
+impl Recording {
+    /// Encodes the recording in the form saved to files
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = header(self.seed);
+        let mut last = Duration::ZERO;
+        for (time, event) in &self.events {
+            encode(&mut bytes, time.saturating_sub(last), event);
+            last = *time;
+        }
+        bytes
+    }
+
+    /// Decodes a recording from the form saved to files
+    ///
+    /// A record cut short, as left by a program which crashed while
+    /// recording, ends the recording rather than failing, while records
+    /// which can't have been written by a recorder fail.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        let mut reader = Reader {
+            bytes,
+            position: 0,
+            error: None,
+        };
+        if reader.take(MAGIC.len()) != Some(MAGIC) {
+            return Err("Not an input recording");
+        }
+        if reader.byte() != Some(VERSION) {
+            return Err("Unsupported input recording version");
+        }
+        let seed = reader
+            .take(8)
+            .and_then(|seed| Some(u64::from_le_bytes(seed.try_into().ok()?)))
+            .ok_or("Input recording is cut short")?;
+
+        let mut events = Vec::new();
+        let mut time = Duration::ZERO;
+        while reader.position < bytes.len() {
+            match decode(&mut reader) {
+                Some(Ok((delta, event))) => {
+                    time = time
+                        .checked_add(delta)
+                        .ok_or("Invalid time in input recording")?;
+                    events.push((time, event));
+                }
+                Some(Err(error)) => return Err(error),
+                None => match reader.error {
+                    Some(error) => return Err(error),
+                    None => break,
+                },
+            }
+        }
+
+        Ok(Self { seed, events })
+    }
+
+    /// Reads a recording from a file
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, &'static str> {
+        Self::from_bytes(&std::fs::read(path).or(Err("Failed to read input recording"))?)
+    }
+
+    /// Writes the recording to a file
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), &'static str> {
+        std::fs::write(path, self.to_bytes()).or(Err("Failed to write input recording"))
+    }
+}
+
+/// Writes every input event to a file as it happens, until stopped.
+///
+/// Events are written as they arrive, so a recording survives the program
+/// crashing. Starting a recorder seeds ```core::rand``` on the calling
+/// thread, so a game using it there plays out the same way on replay.
+pub struct Recorder {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<(), &'static str>>>,
+}
+
+impl Recorder {
+    /// Starts recording to a writer, with a new seed
+    pub fn start(mut writer: impl Write + Send + 'static) -> Result<Self, &'static str> {
+        let seed: u64 = rand::rand();
+        rand::seed(seed);
+
+        writer
+            .write_all(&header(seed))
+            .and_then(|_| writer.flush())
+            .or(Err("Failed to write input recording"))?;
+
+        let events = keyboard::subscribe();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let mut last = Instant::now();
+            let mut bytes = Vec::new();
+
+            while !stopped.load(Ordering::SeqCst) {
+                let event = match events.recv_timeout(STOP_INTERVAL) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    // Only happens when events aren't written as fast as
+                    // they arrive
+                    Err(RecvTimeoutError::Disconnected) => {
+                        return Err("Input recorder fell behind");
+                    }
+                };
+
+                let now = Instant::now();
+                bytes.clear();
+                encode(&mut bytes, now - last, &event);
+                last = now;
+
+                writer
+                    .write_all(&bytes)
+                    .and_then(|_| writer.flush())
+                    .or(Err("Failed to write input recording"))?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Starts recording to a file, replacing it if it exists
+    pub fn create(path: impl AsRef<std::path::Path>) -> Result<Self, &'static str> {
+        let file = std::fs::File::create(path).or(Err("Failed to create input recording"))?;
+        Self::start(std::io::BufWriter::new(file))
+    }
+
+    /// Stops recording, returning whether every event could be written
+    pub fn stop(mut self) -> Result<(), &'static str> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<(), &'static str> {
+        self.stop.store(true, Ordering::SeqCst);
+        match self.thread.take() {
+            Some(thread) => thread.join().or(Err("Input recorder panicked"))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Sends the events of a recording through the keyboard module again, as
+/// if they were being read.
+///
+/// Keys update ```keyboard::keys()``` and call the keyboard callback, and
+/// every event is queued for ```keyboard::poll()``` and sent to
+/// subscribers. Resizes are only sent as events; the size of the terminal
+/// doesn't change. Live input keeps being read unless
+/// ```keyboard::run()``` isn't called.
+///
+/// # Example
+/// ```no_run
+/// # use a5c11::replay::{Recording, Replay};
+/// let recording = Recording::load("bug.rec").unwrap();
+/// let replay = Replay::new(recording).speed(4.0).start();
+/// ```
+pub struct Replay {
+    recording: Recording,
+    speed: f32,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            speed: 1.0,
+        }
+    }
+
+    /// Sets how many times faster than recorded to send events, with
+    /// ```f32::INFINITY``` sending them all at once
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Seeds ```core::rand``` on the calling thread as it was when the
+    /// events were recorded, and starts sending them on a separate thread
+    pub fn start(self) -> JoinHandle<()> {
+        rand::seed(self.recording.seed);
+
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            for (time, event) in self.recording.events {
+                if self.speed > 0.0 && self.speed.is_finite() {
+                    let due = start + time.div_f32(self.speed);
+                    std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                }
+                keyboard::emit(event);
+            }
+        })
+    }
+}
+
+/// Returns the bytes a recording starts with
+fn header(seed: u64) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend(seed.to_le_bytes());
+    bytes
+}
+
+/// Writes a number in as few bytes as it needs, seven bits at a time
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_string(bytes: &mut Vec<u8>, text: &str) {
+    write_varint(bytes, text.len() as u64);
+    bytes.extend(text.as_bytes());
+}
+
+/// Writes the record of an event which happened some time after the
+/// previous one
+fn encode(bytes: &mut Vec<u8>, delta: Duration, event: &Event) {
+    write_varint(bytes, delta.as_micros() as u64);
+
+    match event {
+        Event::Key(event) => {
+            bytes.push(0);
+            write_varint(bytes, event.key as u64);
+            bytes.push(event.down as u8 | (event.repeat as u8) << 1);
+            bytes.push(event.modifiers.bits());
+            // Text is written with its length plus one, so no text is zero
+            match &event.text {
+                Some(text) => {
+                    write_varint(bytes, text.len() as u64 + 1);
+                    bytes.extend(text.as_bytes());
+                }
+                None => write_varint(bytes, 0),
+            }
+        }
+        Event::Text(text) => {
+            bytes.push(1);
+            write_string(bytes, text);
+        }
+        Event::Mouse(event) => {
+            bytes.push(2);
+            bytes.push(
+                MOUSE_KINDS
+                    .iter()
+                    .position(|kind| *kind == event.kind)
+                    .unwrap() as u8,
+            );
+            bytes.push(
+                MOUSE_BUTTONS
+                    .iter()
+                    .position(|button| *button == event.button)
+                    .unwrap() as u8,
+            );
+            write_varint(bytes, event.x as u64);
+            write_varint(bytes, event.y as u64);
+            bytes.push(event.modifiers.bits());
+        }
+        Event::Resize(width, height) => {
+            bytes.push(3);
+            write_varint(bytes, *width as u64);
+            write_varint(bytes, *height as u64);
+        }
+        Event::Paste(text) => {
+            bytes.push(4);
+            write_string(bytes, text);
+        }
+        Event::FocusGained => bytes.push(5),
+        Event::FocusLost => bytes.push(6),
+        Event::GamepadConnected(id) => {
+            bytes.push(7);
+            write_varint(bytes, *id as u64);
+        }
+        Event::GamepadDisconnected(id) => {
+            bytes.push(8);
+            write_varint(bytes, *id as u64);
+        }
+        Event::Tick => bytes.push(9),
+        Event::Error(error) => {
+            bytes.push(10);
+            write_string(bytes, error);
+        }
+    }
+}
+
+/// Reads through the bytes of a recording
+///
+/// Reads return ```None``` when the recording is cut short, or when it's
+/// corrupt, in which case ```error``` is set as well.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    error: Option<&'static str>,
+}
+
+impl<'a> Reader<'a> {
+    fn fail<T>(&mut self, error: &'static str) -> Option<T> {
+        self.error = Some(error);
+        None
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let Some(end) = self.position.checked_add(len) else {
+            return self.fail("Invalid length in input recording");
+        };
+        let bytes = self.bytes.get(self.position..end)?;
+        self.position += len;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        self.fail("Invalid number in input recording")
+    }
+
+    /// Reads a varint which has to fit in a smaller type
+    fn number<T: TryFrom<u64>>(&mut self) -> Option<T> {
+        match T::try_from(self.varint()?) {
+            Ok(number) => Some(number),
+            Err(_) => self.fail("Invalid number in input recording"),
+        }
+    }
+
+    fn string(&mut self, len: u64) -> Option<String> {
+        let Ok(len) = usize::try_from(len) else {
+            return self.fail("Invalid length in input recording");
+        };
+        match String::from_utf8(self.take(len)?.to_vec()) {
+            Ok(string) => Some(string),
+            Err(_) => self.fail("Invalid text in input recording"),
+        }
+    }
+}
+
+/// Reads the record of an event, returning ```None``` if it was cut short
+/// or the reader found it corrupt
+fn decode(reader: &mut Reader) -> Option<Result<(Duration, Event), &'static str>> {
+    let delta = Duration::from_micros(reader.varint()?);
+
+    let event = match reader.byte()? {
+        0 => {
+            let code = reader.varint()?;
+            let Some(key) = Key::ALL.into_iter().find(|key| *key as u64 == code) else {
+                return Some(Err("Unknown key in input recording"));
+            };
+            let flags = reader.byte()?;
+            let modifiers = Modifiers::from_bits(reader.byte()?);
+            let text = match reader.varint()? {
+                0 => None,
+                len => Some(reader.string(len - 1)?),
+            };
+
+            Event::Key(KeyEvent {
+                key,
+                down: flags & 1 != 0,
+                modifiers,
+                repeat: flags & 2 != 0,
+                text,
+            })
+        }
+        1 => {
+            let len = reader.varint()?;
+            Event::Text(reader.string(len)?)
+        }
+        2 => {
+            let (Some(kind), Some(button)) = (
+                MOUSE_KINDS.get(reader.byte()? as usize),
+                MOUSE_BUTTONS.get(reader.byte()? as usize),
+            ) else {
+                return Some(Err("Unknown mouse event in input recording"));
+            };
+
+            Event::Mouse(MouseEvent {
+                kind: *kind,
+                button: *button,
+                x: reader.number()?,
+                y: reader.number()?,
+                modifiers: Modifiers::from_bits(reader.byte()?),
+            })
+        }
+        3 => Event::Resize(reader.number()?, reader.number()?),
+        4 => {
+            let len = reader.varint()?;
+            Event::Paste(reader.string(len)?)
+        }
+        5 => Event::FocusGained,
+        6 => Event::FocusLost,
+        7 => Event::GamepadConnected(reader.number()?),
+        8 => Event::GamepadDisconnected(reader.number()?),
+        9 => Event::Tick,
+        10 => {
+            let len = reader.varint()?;
+            Event::Error(reader.string(len)?)
+        }
+        _ => return Some(Err("Unknown event in input recording")),
+    };
+
+    Some(Ok((delta, event)))
+}
@@ -1,5 +1,5 @@
 use crate::core::{
-    rand::{range, vec_range},
+    rand::vec_range,
     terminal::{rgb, Element},
     Position, Sprite,
 };
@@ -135,8 +135,7 @@ impl Sprite for Checkerboard {
     // Update generate_elements to use the custom colors
     fn elements(&self) -> Vec<(crate::core::terminal::Element, Position)> {
         (0..(self.height * self.width))
-            .enumerate()
-            .map(|(_, i)| {
+            .map(|i| {
                 let x = i % self.width;
                 let y = (i - x) / self.width;
 
@@ -146,7 +145,7 @@ impl Sprite for Checkerboard {
                 let fg_code;
                 let bg_code;
 
-                if self.alt == false {
+                if !self.alt {
                     fg_code = self.fg_color1;
                     bg_code = self.bg_color1;
                 } else {
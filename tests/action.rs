@@ -0,0 +1,115 @@
+use a5c11::{
+    action::{ActionMap, Binding},
+    keyboard::{Event, Key, KeyEvent, Modifiers, MouseButton, MouseEvent, MouseKind},
+};
+
+fn mouse(kind: MouseKind, button: MouseButton) -> Event {
+    Event::Mouse(MouseEvent {
+        kind,
+        button,
+        x: 0,
+        y: 0,
+        modifiers: Modifiers::NONE,
+    })
+}
+
+#[test]
+fn save_and_load() {
+    let mut actions = ActionMap::new();
+    actions.bind("fire", Key::LeftCtrl);
+    actions.bind("fire", MouseButton::Left);
+    actions.bind_axis("move_x", Key::A, Key::D);
+    actions.bind_axis("move_x", Key::DPadLeft, Key::DPadRight);
+
+    let path = std::env::temp_dir().join(format!("a5c11-bindings-{}", std::process::id()));
+    actions.save(&path).unwrap();
+    let loaded = ActionMap::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let loaded = loaded.unwrap();
+    assert_eq!(
+        loaded.bindings("fire"),
+        [
+            Binding::Key(Key::LeftCtrl),
+            Binding::Mouse(MouseButton::Left)
+        ]
+    );
+    assert_eq!(
+        loaded.axis_bindings("move_x"),
+        [
+            (Binding::Key(Key::A), Binding::Key(Key::D)),
+            (Binding::Key(Key::DPadLeft), Binding::Key(Key::DPadRight)),
+        ]
+    );
+    assert_eq!(loaded.to_string(), actions.to_string());
+
+    assert!(ActionMap::load(&path).is_err());
+
+    // Names which couldn't be loaded again aren't bound in the first place
+    for name in ["big jump", "", "a=b", "#jump"] {
+        let bound = std::panic::catch_unwind(|| ActionMap::new().bind(name, Key::Space));
+        assert!(bound.is_err(), "{name:?}");
+        let bound = std::panic::catch_unwind(|| ActionMap::new().bind_axis(name, Key::A, Key::D));
+        assert!(bound.is_err(), "{name:?}");
+    }
+}
+
+#[test]
+fn no_button_has_a_name() {
+    assert_eq!(Binding::Mouse(MouseButton::None).to_string(), "MouseNone");
+    assert!("MouseNone".parse::<Binding>().is_err());
+}
+
+#[test]
+fn parse_errors() {
+    let parse = |text: &str| text.parse::<ActionMap>().map(|map| map.to_string());
+
+    assert_eq!(
+        parse("# comment\n\n  jump = Space  \n"),
+        Ok("jump = Space\n".to_string())
+    );
+    assert_eq!(parse("jump Space"), Err("Missing = in bindings"));
+    assert_eq!(parse(" = Space"), Err("Invalid action name in bindings"));
+    assert_eq!(
+        parse("big jump = Space"),
+        Err("Invalid action name in bindings")
+    );
+    assert_eq!(parse("jump = Spacebar"), Err("Unknown binding name"));
+    assert_eq!(parse("move_x = A/Right/"), Err("Unknown binding name"));
+    assert_eq!(parse("fire = MouseNone"), Err("Unknown binding name"));
+}
+
+#[test]
+#[should_panic(expected = "MouseButton::None can't be bound")]
+fn bind_no_button() {
+    ActionMap::new().bind("fire", MouseButton::None);
+}
+
+#[test]
+#[should_panic(expected = "MouseButton::None can't be bound")]
+fn bind_axis_no_button() {
+    ActionMap::new().bind_axis("move_x", Key::A, MouseButton::None);
+}
+
+#[test]
+fn focus_lost_releases() {
+    let mut actions: ActionMap = "jump = Space\nfire = MouseLeft\nmove_x = A/D"
+        .parse()
+        .unwrap();
+    actions.update(&[
+        Event::Key(KeyEvent::new(Key::Space, true)),
+        Event::Key(KeyEvent::new(Key::D, true)),
+        mouse(MouseKind::Press, MouseButton::Left),
+    ]);
+    assert!(actions.pressed("jump") && actions.pressed("fire"));
+    assert_eq!(actions.axis("move_x"), 1.0);
+
+    // The releases are never seen once the terminal loses focus
+    actions.update(&[Event::FocusLost]);
+    assert!(actions.just_released("jump") && actions.just_released("fire"));
+    assert!(!actions.pressed("jump") && !actions.pressed("fire"));
+    assert_eq!(actions.axis("move_x"), 0.0);
+
+    actions.update(&[Event::Key(KeyEvent::new(Key::Space, false))]);
+    assert!(!actions.just_released("jump"));
+}
@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use a5c11::keyboard::{Chord, Key, KeyEvent, Modifiers, Repeat, Repeater, Sequence};
+
+fn down(key: Key, modifiers: Modifiers) -> KeyEvent {
+    KeyEvent {
+        modifiers,
+        ..KeyEvent::new(key, true)
+    }
+}
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[test]
+fn chord_matches_exact_modifiers() {
+    let chord: Chord = "Ctrl+Shift+P".parse().unwrap();
+
+    assert!(chord.matches(&down(Key::P, Modifiers::CTRL | Modifiers::SHIFT)));
+    assert!(!chord.matches(&down(Key::P, Modifiers::CTRL)));
+    assert!(!chord.matches(&down(
+        Key::P,
+        Modifiers::CTRL | Modifiers::SHIFT | Modifiers::ALT
+    )));
+    assert!(!chord.matches(&KeyEvent {
+        down: false,
+        ..down(Key::P, Modifiers::CTRL | Modifiers::SHIFT)
+    }));
+}
+
+#[test]
+fn sequence_progress() {
+    let mut sequence: Sequence = "g Shift+g d".parse().unwrap();
+    let start = Instant::now();
+    assert_eq!(sequence.progress(), 0);
+
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::NONE), start));
+    assert_eq!(sequence.progress(), 1);
+
+    // Modifier keys, releases and repeats don't break the sequence
+    assert!(!sequence.feed_at(&down(Key::LeftShift, Modifiers::SHIFT), start + ms(10)));
+    assert!(!sequence.feed_at(&KeyEvent::new(Key::G, false), start + ms(10)));
+    assert!(!sequence.feed_at(
+        &KeyEvent {
+            repeat: true,
+            ..down(Key::G, Modifiers::NONE)
+        },
+        start + ms(10)
+    ));
+    assert_eq!(sequence.progress(), 1);
+
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::SHIFT), start + ms(20)));
+    assert_eq!(sequence.progress(), 2);
+
+    // A wrong chord starts the sequence over
+    assert!(!sequence.feed_at(&down(Key::X, Modifiers::NONE), start + ms(30)));
+    assert_eq!(sequence.progress(), 0);
+
+    // Pressing the first chord again keeps it as the start of the sequence
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::NONE), start + ms(40)));
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::NONE), start + ms(50)));
+    assert_eq!(sequence.progress(), 1);
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::SHIFT), start + ms(60)));
+    assert!(sequence.feed_at(&down(Key::D, Modifiers::NONE), start + ms(70)));
+    assert_eq!(sequence.progress(), 0);
+}
+
+#[test]
+fn sequence_timeout() {
+    let mut sequence =
+        Sequence::new(vec![Chord::from(Key::G), Chord::from(Key::G)]).with_timeout(ms(100));
+    let start = Instant::now();
+
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::NONE), start));
+    assert!(sequence.feed_at(&down(Key::G, Modifiers::NONE), start + ms(100)));
+
+    // The second press came too late, so it starts the sequence again
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::NONE), start + ms(200)));
+    assert!(!sequence.feed_at(&down(Key::G, Modifiers::NONE), start + ms(301)));
+    assert_eq!(sequence.progress(), 1);
+    assert!(sequence.feed_at(&down(Key::G, Modifiers::NONE), start + ms(350)));
+
+    // Each chord gets its own timeout, rather than the whole sequence
+    let mut sequence = "a b c".parse::<Sequence>().unwrap().with_timeout(ms(900));
+    assert!(!sequence.feed_at(&down(Key::A, Modifiers::NONE), start));
+    assert!(!sequence.feed_at(&down(Key::B, Modifiers::NONE), start + ms(800)));
+    assert!(sequence.feed_at(&down(Key::C, Modifiers::NONE), start + ms(1600)));
+}
+
+#[test]
+fn synthesized_repeats() {
+    let mut repeater = Repeater::new(Repeat {
+        delay: ms(500),
+        interval: ms(100),
+    });
+    let start = Instant::now();
+    assert_eq!(repeater.next(), None);
+
+    assert!(repeater.feed_at(&down(Key::LeftShift, Modifiers::SHIFT), start));
+    assert_eq!(repeater.next(), None, "modifiers don't repeat");
+
+    let a = down(Key::A, Modifiers::SHIFT);
+    assert!(repeater.feed_at(&a, start));
+    assert!(repeater.due(start + ms(499)).is_empty());
+
+    // Repeats from the operating system are dropped
+    assert!(!repeater.feed_at(
+        &KeyEvent {
+            repeat: true,
+            ..a.clone()
+        },
+        start + ms(30)
+    ));
+
+    let repeat = KeyEvent {
+        repeat: true,
+        ..a.clone()
+    };
+    assert_eq!(repeater.due(start + ms(500)), vec![repeat.clone()]);
+    assert!(repeater.due(start + ms(599)).is_empty());
+    assert_eq!(repeater.due(start + ms(600)), vec![repeat.clone()]);
+
+    // Missed repeats are skipped rather than sent all at once
+    assert_eq!(repeater.due(start + ms(1000)), vec![repeat]);
+    assert_eq!(repeater.next(), Some(start + ms(1100)));
+
+    assert!(repeater.feed_at(&KeyEvent::new(Key::A, false), start + ms(1050)));
+    assert_eq!(repeater.next(), None);
+    assert!(repeater.due(start + ms(2000)).is_empty());
+}
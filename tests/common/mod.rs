@@ -0,0 +1,13 @@
+//! Helpers shared by the tests which read Linux input devices
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+
+/// Returns the bytes of an ```input_event``` struct as read from a device
+pub fn record(event_type: u16, code: u16, value: i32) -> Vec<u8> {
+    let mut bytes = vec![0u8; std::mem::size_of::<libc::input_event>() - 8];
+    bytes.extend(event_type.to_ne_bytes());
+    bytes.extend(code.to_ne_bytes());
+    bytes.extend(value.to_ne_bytes());
+    bytes
+}
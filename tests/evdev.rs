@@ -0,0 +1,96 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use a5c11::keyboard::{evdev::Parser, Event, Key, KeyEvent, Modifiers};
+use common::{record, EV_KEY, EV_SYN};
+
+const EV_MSC: u16 = 0x04;
+
+/// Returns the bytes of a key event, with the scan code and report which
+/// keyboards send around it
+fn key(code: u16, value: i32) -> Vec<u8> {
+    [
+        record(EV_MSC, 4, 0x7001e),
+        record(EV_KEY, code, value),
+        record(EV_SYN, 0, 0),
+    ]
+    .concat()
+}
+
+fn key_event(key: Key, down: bool, modifiers: Modifiers, repeat: bool) -> Event {
+    Event::Key(KeyEvent {
+        modifiers,
+        repeat,
+        ..KeyEvent::new(key, down)
+    })
+}
+
+#[test]
+fn press_repeat_and_release() {
+    let stream = [
+        key(42, 1), // Left Shift
+        key(30, 1), // A
+        key(30, 2),
+        key(30, 0),
+        key(42, 0),
+    ]
+    .concat();
+
+    let mut parser = Parser::new();
+    let events = parser.feed(&stream);
+
+    assert_eq!(
+        events,
+        vec![
+            key_event(Key::LeftShift, true, Modifiers::SHIFT, false),
+            key_event(Key::A, true, Modifiers::SHIFT, false),
+            key_event(Key::A, true, Modifiers::SHIFT, true),
+            key_event(Key::A, false, Modifiers::SHIFT, false),
+            key_event(Key::LeftShift, false, Modifiers::NONE, false),
+        ]
+    );
+    assert!(parser.held().is_empty());
+}
+
+#[test]
+fn structs_split_across_reads() {
+    let stream = [key(1, 1), key(1, 0)].concat();
+
+    let mut parser = Parser::new();
+    let mut events = Vec::new();
+    for chunk in stream.chunks(5) {
+        events.extend(parser.feed(chunk));
+    }
+
+    assert_eq!(
+        events,
+        vec![
+            key_event(Key::Escape, true, Modifiers::NONE, false),
+            key_event(Key::Escape, false, Modifiers::NONE, false),
+        ]
+    );
+}
+
+#[test]
+fn held_keys_are_tracked() {
+    let mut parser = Parser::new();
+    parser.feed(&[key(29, 1), key(103, 1)].concat());
+    assert_eq!(parser.held(), [Key::LeftCtrl, Key::Up]);
+
+    let events = parser.feed(&key(57, 1));
+    assert_eq!(
+        events,
+        vec![key_event(Key::Space, true, Modifiers::CTRL, false)]
+    );
+}
+
+#[test]
+fn other_codes_are_ignored() {
+    let mut parser = Parser::new();
+
+    // Mouse buttons, and a key code without a key
+    let events = parser.feed(&[key(0x110, 1), key(0x2fe, 1)].concat());
+    assert!(events.is_empty());
+    assert!(parser.held().is_empty());
+}
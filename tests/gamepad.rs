@@ -0,0 +1,122 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use a5c11::{
+    gamepad::{Axis, Input, Parser},
+    keyboard::Key,
+};
+use common::{record, EV_KEY, EV_SYN};
+
+const EV_ABS: u16 = 0x03;
+
+fn report(events: &[(u16, u16, i32)]) -> Vec<u8> {
+    events
+        .iter()
+        .flat_map(|(event_type, code, value)| record(*event_type, *code, *value))
+        .chain(record(EV_SYN, 0, 0))
+        .collect()
+}
+
+#[test]
+fn buttons() {
+    let stream = [
+        report(&[(EV_KEY, 0x130, 1)]), // South
+        report(&[(EV_KEY, 0x13b, 1)]), // Start
+        report(&[(EV_KEY, 0x130, 0)]),
+        report(&[(EV_KEY, 0x13b, 0)]),
+    ]
+    .concat();
+
+    let mut parser = Parser::new();
+    assert_eq!(
+        parser.feed(&stream),
+        vec![
+            Input::Button(Key::South, true),
+            Input::Button(Key::Start, true),
+            Input::Button(Key::South, false),
+            Input::Button(Key::Start, false),
+        ]
+    );
+    assert!(parser.held().is_empty());
+}
+
+#[test]
+fn hat_is_a_dpad() {
+    let stream = [
+        report(&[(EV_ABS, 0x10, -1)]),
+        report(&[(EV_ABS, 0x10, 1)]),
+        report(&[(EV_ABS, 0x11, 1)]),
+        report(&[(EV_ABS, 0x10, 0), (EV_ABS, 0x11, 0)]),
+    ]
+    .concat();
+
+    let mut parser = Parser::new();
+    assert_eq!(
+        parser.feed(&stream),
+        vec![
+            Input::Button(Key::DPadLeft, true),
+            Input::Button(Key::DPadLeft, false),
+            Input::Button(Key::DPadRight, true),
+            Input::Button(Key::DPadDown, true),
+            Input::Button(Key::DPadRight, false),
+            Input::Button(Key::DPadDown, false),
+        ]
+    );
+}
+
+#[test]
+fn axes_are_scaled() {
+    let mut parser = Parser::new();
+    parser.set_range(Axis::LeftX, 0, 255);
+    parser.set_range(Axis::RightTrigger, 0, 255);
+
+    let inputs = parser.feed(&report(&[
+        (EV_ABS, 0x00, 0),
+        (EV_ABS, 0x01, 32767),
+        (EV_ABS, 0x05, 51),
+    ]));
+    assert_eq!(
+        inputs,
+        vec![
+            Input::Axis(Axis::LeftX, -1.0),
+            Input::Axis(Axis::LeftY, 1.0),
+            Input::Axis(Axis::RightTrigger, 0.2),
+        ]
+    );
+}
+
+#[test]
+fn pulled_triggers_are_pressed() {
+    let mut parser = Parser::new();
+    parser.set_range(Axis::LeftTrigger, 0, 100);
+
+    let mut inputs = parser.feed(&report(&[(EV_ABS, 0x02, 60)]));
+    // Gamepads reporting the trigger as a button too don't press it twice
+    inputs.extend(parser.feed(&report(&[(EV_KEY, 0x138, 1)])));
+    inputs.extend(parser.feed(&report(&[(EV_ABS, 0x02, 10)])));
+
+    assert_eq!(
+        inputs,
+        vec![
+            Input::Axis(Axis::LeftTrigger, 0.6),
+            Input::Button(Key::LeftTrigger, true),
+            Input::Axis(Axis::LeftTrigger, 0.1),
+            Input::Button(Key::LeftTrigger, false),
+        ]
+    );
+}
+
+#[test]
+fn keyboard_keys_are_ignored() {
+    let mut parser = Parser::new();
+
+    // Escape, and a button repeating
+    let stream = [
+        report(&[(EV_KEY, 1, 1)]),
+        report(&[(EV_KEY, 0x131, 1)]),
+        report(&[(EV_KEY, 0x131, 2)]),
+    ]
+    .concat();
+    assert_eq!(parser.feed(&stream), vec![Input::Button(Key::East, true)]);
+}
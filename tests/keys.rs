@@ -0,0 +1,134 @@
+use a5c11::keyboard::{Event, Key, Parser};
+
+#[test]
+fn hid_codes_round_trip() {
+    for key in Key::ALL {
+        if let Some((page, usage)) = key.to_hid() {
+            assert_eq!(Key::from_hid(page, usage), Some(key), "{key:?}");
+        }
+    }
+
+    for code in 0..=u8::MAX {
+        if let Some(key) = Key::from_code(code) {
+            assert_eq!(key.to_code(), Some(code), "{code:#04x}");
+        }
+    }
+}
+
+#[test]
+fn hid_codes_match_discriminants() {
+    for key in Key::ALL {
+        if let Some(code) = key.to_code() {
+            assert_eq!(key as u32, code as u32, "{key:?}");
+        }
+    }
+
+    assert_eq!(Key::from_code(Key::F13 as u8), Some(Key::F13));
+    assert_eq!(Key::from_code(Key::Up as u8), Some(Key::Up));
+    assert_eq!(Key::from_code(Key::Right as u8), Some(Key::Right));
+    assert_eq!(Key::from_code(0), None);
+}
+
+#[test]
+fn keyboard_keys_have_codes() {
+    let keyboard = Key::ALL.iter().take_while(|key| **key != Key::DPadUp);
+
+    for key in keyboard {
+        assert!(key.to_hid().is_some(), "{key:?} has no HID usage");
+        assert!(key.to_evdev().is_some(), "{key:?} has no evdev code");
+        assert!(key.to_sequence().is_some(), "{key:?} has no sequence");
+    }
+
+    assert_eq!(Key::Escape.to_virtual_key(), Some(0x1b));
+    assert_eq!(Key::Escape.to_evdev(), Some(1));
+}
+
+#[test]
+fn virtual_keys_round_trip() {
+    for key in Key::ALL {
+        if let Some(code) = key.to_virtual_key() {
+            assert_eq!(Key::from_virtual_key(code), Some(key), "{key:?}");
+        }
+    }
+
+    // Virtual-key codes aren't HID usages
+    assert_eq!(Key::from_virtual_key(0x41), Some(Key::A));
+    assert_eq!(Key::from_virtual_key(0x26), Some(Key::Up));
+    assert_eq!(Key::from_virtual_key(0x10), Some(Key::LeftShift));
+}
+
+#[test]
+fn evdev_codes_round_trip() {
+    for key in Key::ALL {
+        if let Some(code) = key.to_evdev() {
+            assert_eq!(Key::from_evdev(code), Some(key), "{key:?}");
+        }
+    }
+
+    for code in 0..=u16::MAX {
+        if let Some(key) = Key::from_evdev(code) {
+            assert_eq!(key.to_evdev(), Some(code), "{code}");
+        }
+    }
+}
+
+#[test]
+fn terminal_sequences_round_trip() {
+    for key in Key::ALL {
+        let Some(sequence) = key.to_sequence() else {
+            continue;
+        };
+
+        let mut parser = Parser::new();
+        let mut events = parser.feed(sequence.as_bytes());
+        events.extend(parser.flush());
+
+        match events.first() {
+            Some(Event::Key(event)) => {
+                assert_eq!(event.key, key, "{sequence:?}");
+                assert!(event.down, "{sequence:?}");
+                assert!(event.modifiers.is_empty(), "{sequence:?}");
+            }
+            other => panic!("{sequence:?} was parsed as {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn pastes_across_reads() {
+    let mut parser = Parser::new();
+    let stream = b"a\x1b[200~pasted\r\x1b[A text\x1b[201~b";
+
+    // Every split of the markers has to be found, one byte at a time
+    let events: Vec<Event> = stream
+        .iter()
+        .flat_map(|byte| parser.feed(&[*byte]))
+        .collect();
+    assert_eq!(events, Parser::new().feed(stream));
+    assert!(events.contains(&Event::Paste("pasted\n\x1b[A text".to_string())));
+    assert!(!parser.pasting() && !parser.pending());
+}
+
+#[test]
+fn unfinished_pastes_are_flushed() {
+    let mut parser = Parser::new();
+    assert!(parser.feed(b"\x1b[200~no end").is_empty());
+    assert!(parser.pasting());
+    assert!(!parser.pending());
+
+    assert_eq!(parser.flush(), [Event::Paste("no end".to_string())]);
+    assert!(!parser.pasting());
+
+    // A paste which never ends stops being held on to
+    assert!(parser.feed(b"\x1b[200~").is_empty());
+    let chunk = vec![b'x'; 64 * 1024];
+    let events = (0..32)
+        .map(|_| parser.feed(&chunk))
+        .find(|events| !events.is_empty())
+        .unwrap();
+    match &events[..] {
+        [Event::Paste(text)] => assert!(text.len() > 1 << 20),
+        events => panic!("no paste in {events:?}"),
+    }
+    assert!(!parser.pasting());
+}
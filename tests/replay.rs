@@ -0,0 +1,158 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use a5c11::{
+    core::rand,
+    keyboard::{self, Event, Key, KeyEvent, Modifiers, MouseButton, MouseEvent, MouseKind},
+    replay::{Recorder, Recording, Replay},
+};
+
+/// Writer whose bytes can still be read after it's given to a recorder
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn events() -> Vec<Event> {
+    vec![
+        Event::Key(KeyEvent {
+            modifiers: Modifiers::SHIFT,
+            text: Some("A".to_string()),
+            ..KeyEvent::new(Key::A, true)
+        }),
+        Event::Key(KeyEvent {
+            repeat: true,
+            ..KeyEvent::new(Key::PlayPause, true)
+        }),
+        Event::Key(KeyEvent::new(Key::South, false)),
+        Event::Text("é".to_string()),
+        Event::Mouse(MouseEvent {
+            kind: MouseKind::Drag,
+            button: MouseButton::Right,
+            x: 300,
+            y: 2,
+            modifiers: Modifiers::CTRL | Modifiers::ALT,
+        }),
+        Event::Resize(200, 60),
+        Event::Paste("line\nline".to_string()),
+        Event::FocusGained,
+        Event::FocusLost,
+        Event::GamepadConnected(1),
+        Event::GamepadDisconnected(1),
+        Event::Error("Failed to poll input devices".to_string()),
+    ]
+}
+
+#[test]
+fn every_event_round_trips() {
+    let recording = Recording {
+        seed: u64::MAX,
+        events: events()
+            .into_iter()
+            .enumerate()
+            .map(|(i, event)| (Duration::from_micros(i as u64 * 70_001), event))
+            .collect(),
+    };
+
+    assert_eq!(Recording::from_bytes(&recording.to_bytes()), Ok(recording));
+}
+
+#[test]
+fn cut_short_recordings_keep_whole_records() {
+    let recording = Recording {
+        seed: 7,
+        events: vec![
+            (Duration::ZERO, Event::FocusGained),
+            (Duration::from_millis(5), Event::Paste("pasted".to_string())),
+        ],
+    };
+
+    let bytes = recording.to_bytes();
+    let cut = Recording::from_bytes(&bytes[..bytes.len() - 2]).unwrap();
+    assert_eq!(cut.events, recording.events[..1]);
+
+    assert!(Recording::from_bytes(b"not a recording").is_err());
+}
+
+#[test]
+fn corrupt_recordings_fail() {
+    let header = Recording {
+        seed: 7,
+        events: Vec::new(),
+    }
+    .to_bytes();
+    let corrupt = |record: &[u8]| Recording::from_bytes(&[&header[..], record].concat());
+
+    // A text event with invalid UTF-8
+    assert_eq!(
+        corrupt(&[0, 1, 2, 0xc3, 0x28]),
+        Err("Invalid text in input recording")
+    );
+    // A paste whose length overflows
+    let mut record = vec![0, 4];
+    record.extend([0xff; 9]);
+    record.push(0x01);
+    assert_eq!(corrupt(&record), Err("Invalid length in input recording"));
+    // A mouse event at a column past what fits in 16 bits
+    assert_eq!(
+        corrupt(&[0, 2, 0, 1, 0x80, 0x80, 0x04, 0, 0]),
+        Err("Invalid number in input recording")
+    );
+    // A number longer than 64 bits
+    assert_eq!(
+        corrupt(&[0xff; 10]),
+        Err("Invalid number in input recording")
+    );
+}
+
+#[test]
+fn recorded_events_are_replayed() {
+    let writer = Shared::default();
+    let recorder = Recorder::start(writer.clone()).unwrap();
+    let expected: u32 = rand::rand();
+
+    let events = events();
+    for event in &events {
+        keyboard::push(event.clone());
+    }
+
+    // Wait for the recorder to write every event before stopping it
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Recording::from_bytes(&writer.0.lock().unwrap())
+        .unwrap()
+        .events
+        .len()
+        < events.len()
+    {
+        assert!(Instant::now() < deadline, "events weren't recorded");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    recorder.stop().unwrap();
+
+    let recording = Recording::from_bytes(&writer.0.lock().unwrap()).unwrap();
+    let recorded: Vec<Event> = recording.events.iter().map(|(_, e)| e.clone()).collect();
+    assert_eq!(recorded, events);
+
+    let subscriber = keyboard::subscribe();
+    Replay::new(recording)
+        .speed(f32::INFINITY)
+        .start()
+        .join()
+        .unwrap();
+    assert_eq!(rand::rand::<u32>(), expected);
+
+    let replayed: Vec<Event> = subscriber.try_iter().collect();
+    assert_eq!(replayed, events);
+}